@@ -0,0 +1,372 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+use confidential_solana::confidential::DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER;
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use spl_token_client::spl_token_2022::solana_zk_sdk::encryption::pod::elgamal::PodElGamalPubkey;
+
+// =================== CLI Definition ===================
+
+/// Confidential token operations on SPL Token 2022, driven from the command line.
+#[derive(Parser, Debug)]
+#[command(name = "confidential-solana", version, about)]
+pub struct Cli {
+    /// Simulate every transaction instead of sending it, printing its logs and compute units.
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// Solana CLI config file [default: ~/.config/solana/cli/config.yml].
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+    /// JSON RPC URL or moniker (localhost, devnet, testnet, mainnet-beta) [default: from the config file, else http://localhost:8899].
+    #[arg(long, short = 'u', global = true)]
+    pub url: Option<String>,
+    /// WebSocket URL [default: from the config file, else computed from --url].
+    #[arg(long, global = true)]
+    pub ws_url: Option<String>,
+    /// Commitment level of reads and confirmations [default: from the config file, else confirmed].
+    #[arg(long, global = true, value_parser = ["processed", "confirmed", "finalized"])]
+    pub commitment: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+/// Identifies a confidential token account, either by a saved wallet or explicitly.
+#[derive(Args, Debug)]
+pub struct AccountArgs {
+    /// Name of a saved wallet (replaces --mint, --owner and --account).
+    #[arg(long, conflicts_with_all = ["mint", "owner", "account"])]
+    pub wallet: Option<String>,
+    /// Mint address of the confidential token.
+    #[arg(long, required_unless_present = "wallet")]
+    pub mint: Option<Pubkey>,
+    /// Keypair file of the account owner.
+    #[arg(long, required_unless_present = "wallet")]
+    pub owner: Option<PathBuf>,
+    /// Confidential token account (defaults to the owner's associated token account).
+    #[arg(long)]
+    pub account: Option<Pubkey>,
+}
+
+/// All supported confidential token operations.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Create a new mint with the ConfidentialTransferMint extension.
+    CreateMint {
+        /// Keypair file of the mint authority (also pays for the mint).
+        #[arg(long)]
+        authority: PathBuf,
+        /// Keypair file for the new mint account. A fresh keypair is generated if omitted.
+        #[arg(long)]
+        mint_keypair: Option<PathBuf>,
+        /// Number of decimals of the new mint.
+        #[arg(long, default_value_t = 6)]
+        decimals: u8,
+        /// Auditor ElGamal pubkey (base64). Every transfer amount is also encrypted for it.
+        #[arg(long)]
+        auditor: Option<PodElGamalPubkey>,
+        /// Require new accounts to be approved by the authority (disables auto-approval).
+        #[arg(long)]
+        manual_approval: bool,
+        /// Add the ConfidentialMintBurn extension: tokens are minted and burned confidentially.
+        #[arg(long)]
+        confidential_mint_burn: bool,
+        /// Transfer fee in basis points (1 = 0.01%). Fees of confidential transfers are withheld encrypted.
+        #[arg(long)]
+        transfer_fee_basis_points: Option<u16>,
+        /// Maximum transfer fee per transfer, e.g. "1.5" (defaults to no cap).
+        #[arg(long, requires = "transfer_fee_basis_points")]
+        maximum_fee: Option<String>,
+    },
+
+    /// Generate an auditor ElGamal keypair and print its pubkey.
+    CreateAuditor {
+        /// File to write the auditor ElGamal keypair to.
+        #[arg(long)]
+        outfile: PathBuf,
+    },
+
+    /// Decrypt the amount of a confidential transfer with the auditor's ElGamal keypair.
+    Audit {
+        /// Auditor ElGamal keypair file.
+        #[arg(long)]
+        auditor_keypair: PathBuf,
+        /// Signature of the transaction containing the confidential transfer.
+        #[arg(long)]
+        signature: Signature,
+    },
+
+    /// Create and configure a confidential token account for an owner.
+    CreateAccount {
+        /// Mint address of the confidential token.
+        #[arg(long)]
+        mint: Pubkey,
+        /// Keypair file of the account owner (also pays for the account).
+        #[arg(long)]
+        owner: PathBuf,
+        /// Save the owner, token account and mint to the wallet store under this name.
+        #[arg(long)]
+        wallet: Option<String>,
+        /// Incoming credits accepted before the pending balance must be applied.
+        #[arg(long, default_value_t = DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER)]
+        max_pending_credits: u64,
+        /// Create the account at the owner's associated token address instead of a new keypair.
+        #[arg(long)]
+        associated: bool,
+    },
+
+    /// Recreate the confidential keys of a token account from its owner keypair and check them on-chain.
+    Recover {
+        /// Keypair file of the account owner.
+        #[arg(long)]
+        owner: PathBuf,
+        /// Confidential token account to recover.
+        #[arg(long)]
+        account: Pubkey,
+        /// Save the recovered account to the wallet store under this name.
+        #[arg(long)]
+        wallet: Option<String>,
+    },
+
+    /// Approve a confidential token account on a mint that requires manual approval.
+    ApproveAccount {
+        /// Mint address of the confidential token.
+        #[arg(long)]
+        mint: Pubkey,
+        /// Keypair file of the mint's confidential transfer authority.
+        #[arg(long)]
+        authority: PathBuf,
+        /// Confidential token account to approve.
+        #[arg(long)]
+        account: Pubkey,
+    },
+
+    /// Mint public tokens to a token account.
+    Mint {
+        /// Mint address of the confidential token.
+        #[arg(long)]
+        mint: Pubkey,
+        /// Keypair file of the mint authority.
+        #[arg(long)]
+        authority: PathBuf,
+        /// Token account receiving the tokens.
+        #[arg(long)]
+        account: Pubkey,
+        /// Amount of tokens to mint, e.g. "12.345".
+        #[arg(long)]
+        amount: String,
+    },
+
+    /// Mint tokens straight into the pending confidential balance of a token account.
+    ConfidentialMint {
+        /// Mint address of the confidential token (created with --confidential-mint-burn).
+        #[arg(long)]
+        mint: Pubkey,
+        /// Keypair file of the mint authority.
+        #[arg(long)]
+        authority: PathBuf,
+        /// Confidential token account receiving the tokens.
+        #[arg(long)]
+        account: Pubkey,
+        /// Amount of tokens to mint, e.g. "12.345".
+        #[arg(long)]
+        amount: String,
+    },
+
+    /// Burn tokens from the available confidential balance.
+    Burn {
+        #[command(flatten)]
+        account: AccountArgs,
+        /// Amount of tokens to burn, e.g. "12.345".
+        #[arg(long)]
+        amount: String,
+    },
+
+    /// Deposit public tokens into the confidential balance and apply them.
+    Deposit {
+        #[command(flatten)]
+        account: AccountArgs,
+        /// Amount of tokens to deposit, e.g. "12.345".
+        #[arg(long)]
+        amount: String,
+    },
+
+    /// Apply the pending confidential balance to the available balance.
+    Apply {
+        #[command(flatten)]
+        account: AccountArgs,
+    },
+
+    /// Keep applying the pending balance before the pending balance credit counter hits its limit.
+    WatchPending {
+        #[command(flatten)]
+        account: AccountArgs,
+        /// Apply once this many or fewer incoming credits are left before the limit.
+        #[arg(long, default_value_t = 16)]
+        headroom: u64,
+        /// Seconds between two reads of the credit counter (at least 1).
+        #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+    },
+
+    /// Transfer tokens confidentially between two accounts.
+    Transfer {
+        /// Sender's confidential token account.
+        #[command(flatten)]
+        account: AccountArgs,
+        /// Name of the recipient's saved wallet (replaces --recipient-account).
+        #[arg(long, conflicts_with = "recipient_account")]
+        recipient_wallet: Option<String>,
+        /// Recipient's confidential token account. Only the address is needed.
+        #[arg(long, required_unless_present = "recipient_wallet")]
+        recipient_account: Option<Pubkey>,
+        /// Amount of tokens to transfer, e.g. "12.345".
+        #[arg(long)]
+        amount: String,
+        /// Verify the equality and validity proofs inline, in the transfer transaction, where
+        /// they fit. Falls back to proof context state accounts otherwise.
+        #[arg(long)]
+        inline_proofs: bool,
+    },
+
+    /// Withdraw tokens from the confidential balance back to the public balance.
+    Withdraw {
+        #[command(flatten)]
+        account: AccountArgs,
+        /// Amount of tokens to withdraw, e.g. "12.345".
+        #[arg(long)]
+        amount: String,
+    },
+
+    /// Accept incoming confidential transfers to a token account.
+    EnableConfidentialCredits {
+        #[command(flatten)]
+        account: AccountArgs,
+    },
+
+    /// Refuse incoming confidential transfers to a token account.
+    DisableConfidentialCredits {
+        #[command(flatten)]
+        account: AccountArgs,
+    },
+
+    /// Accept incoming public transfers to a token account.
+    EnableNonConfidentialCredits {
+        #[command(flatten)]
+        account: AccountArgs,
+    },
+
+    /// Refuse incoming public transfers to a token account.
+    DisableNonConfidentialCredits {
+        #[command(flatten)]
+        account: AccountArgs,
+    },
+
+    /// Empty a confidential token account and close it, returning its rent to the owner.
+    CloseAccount {
+        #[command(flatten)]
+        account: AccountArgs,
+    },
+
+    /// Rotate the ElGamal key of a token account by moving its balances to a new account.
+    Rotate {
+        #[command(flatten)]
+        account: AccountArgs,
+        /// Save the new account to the wallet store under this name.
+        #[arg(long)]
+        new_wallet: Option<String>,
+        /// On a mint with a transfer fee, accept paying the fee on the moved balances.
+        #[arg(long)]
+        allow_transfer_fee: bool,
+    },
+
+    /// Move the confidential transfer fees withheld in token accounts to the mint.
+    HarvestFees {
+        /// Mint address of the confidential token.
+        #[arg(long)]
+        mint: Pubkey,
+        /// Keypair file paying for the transaction (harvesting is permissionless).
+        #[arg(long)]
+        payer: PathBuf,
+        /// Token accounts holding withheld fees (repeat for several accounts).
+        #[arg(long = "account", required = true)]
+        accounts: Vec<Pubkey>,
+    },
+
+    /// Withdraw the confidential transfer fees withheld in the mint to a fee account.
+    WithdrawFees {
+        /// Mint address of the confidential token.
+        #[arg(long)]
+        mint: Pubkey,
+        /// Keypair file of the mint's withdraw withheld authority (also owns the fee account).
+        #[arg(long)]
+        authority: PathBuf,
+        /// Confidential token account of the authority receiving the fees.
+        #[arg(long)]
+        account: Pubkey,
+    },
+
+    /// Show a token account, its extensions and (with the owner's keys) its confidential balances.
+    Show {
+        /// Name of a saved wallet whose token account is inspected.
+        #[arg(long, conflicts_with_all = ["account", "owner"])]
+        wallet: Option<String>,
+        /// Token account to inspect.
+        #[arg(long, required_unless_present = "wallet")]
+        account: Option<Pubkey>,
+        /// Keypair file of the account owner, used to decrypt the confidential balances.
+        #[arg(long)]
+        owner: Option<PathBuf>,
+    },
+
+    /// List the wallets saved in the wallet store.
+    Wallets,
+
+    /// Encrypt the secrets of a saved wallet with a passphrase and remove the plaintext owner keypair.
+    Lock {
+        /// Name of the saved wallet.
+        #[arg(long)]
+        wallet: String,
+    },
+
+    /// Decrypt a locked wallet and restore its plaintext owner keypair.
+    Unlock {
+        /// Name of the saved wallet.
+        #[arg(long)]
+        wallet: String,
+    },
+
+    /// Fund addresses with SOL from the faucet or a funder keypair, skipping funded ones.
+    Fund {
+        /// Addresses to fund.
+        #[arg(long = "to", required = true, num_args = 1..)]
+        recipients: Vec<Pubkey>,
+        /// SOL each address should hold at least, e.g. "1.5".
+        #[arg(long, default_value = "1")]
+        sol: String,
+        /// Keypair file paying the SOL, for clusters without a faucet (default: airdrop).
+        #[arg(long)]
+        funder: Option<PathBuf>,
+        /// Seconds an airdrop may take, retries included.
+        #[arg(long, default_value_t = 60)]
+        airdrop_timeout: u64,
+    },
+
+    /// Close proof context state accounts left open by failed or interrupted operations.
+    ReclaimProofAccounts {
+        /// Name of a saved wallet whose owner created the proofs (replaces --authority).
+        #[arg(long, conflicts_with = "authority")]
+        wallet: Option<String>,
+        /// Keypair file of the proof account authority (the owner or mint authority).
+        #[arg(long, required_unless_present = "wallet")]
+        authority: Option<PathBuf>,
+        /// Seconds since the last transaction of an account before it counts as stale.
+        #[arg(long, default_value_t = 300)]
+        min_age: u64,
+        /// Also reclaim accounts whose last transaction time is unknown (e.g. pruned from the
+        /// node's history), which could still be in use.
+        #[arg(long)]
+        include_unknown: bool,
+    },
+}
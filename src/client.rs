@@ -0,0 +1,518 @@
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+use spl_token_client::{
+    client::{ProgramClient, ProgramRpcClient},
+    spl_token_2022::{
+        self,
+        extension::{
+            confidential_transfer::ConfidentialTransferAccount, BaseStateWithExtensions,
+            StateWithExtensionsOwned,
+        },
+        solana_zk_sdk::encryption::pod::elgamal::PodElGamalPubkey,
+        state::Mint,
+    },
+    token::{ComputeUnitLimit, Token},
+};
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    confidential::*,
+    config::RpcConfig,
+    error::{ConfidentialError, Result},
+    helper::{
+        decrypt_confidential_balances, derive_confidential_keys,
+        derive_withdraw_withheld_elgamal_keypair, ConfTokenAccountRes, ConfidentialBalances,
+        ConfidentialSender, ProgramRpcClientSendOrSimulate,
+    },
+};
+
+// =================== Confidential Client ===================
+
+/// Facade over the confidential token operations of a single mint.
+///
+/// Holds the program client, the SPL Token client for the mint and the payer of every
+/// transaction. Token accounts are passed by address; their ElGamal keypair and AE key
+/// are derived from the owner keypair on each call. All amounts are in base units.
+///
+/// By default the client talks to an RPC node. Any other `spl_token_client` program client,
+/// such as `ProgramBanksClient` on top of `solana-program-test`, can be used through
+/// [`ConfidentialClient::with_program_client`].
+pub struct ConfidentialClient<T = ProgramRpcClientSendOrSimulate>
+where
+    T: ConfidentialSender,
+{
+    program_client: Arc<dyn ProgramClient<T>>,
+    token: Token<T>,
+    payer: Arc<Keypair>,
+    decimals: u8,
+}
+
+impl ConfidentialClient {
+    /// Creates an RPC client for `mint` with known decimals, e.g. for a mint that is not created yet.
+    pub fn new(rpc_client: Arc<RpcClient>, mint: Pubkey, payer: Keypair, decimals: u8) -> Self {
+        Self::with_program_client(rpc_program_client(rpc_client, false), mint, payer, decimals)
+    }
+
+    /// Creates an RPC client for an existing mint, reading its decimals from the mint account.
+    pub async fn load(rpc_client: Arc<RpcClient>, mint: Pubkey, payer: Keypair) -> Result<Self> {
+        Self::load_with_program_client(rpc_program_client(rpc_client, false), mint, payer).await
+    }
+}
+
+/// Wraps an RPC client so the SPL Token client can send transactions through it.
+/// With `dry_run`, transactions are only simulated and nothing is committed.
+pub fn rpc_program_client(
+    rpc_client: Arc<RpcClient>,
+    dry_run: bool,
+) -> Arc<dyn ProgramClient<ProgramRpcClientSendOrSimulate>> {
+    // To interact with solana programs
+    Arc::new(ProgramRpcClient::new(
+        rpc_client,
+        ProgramRpcClientSendOrSimulate { dry_run },
+    ))
+}
+
+impl<T: ConfidentialSender> ConfidentialClient<T> {
+    /// Creates a client for `mint` with known decimals on top of any program client.
+    pub fn with_program_client(
+        program_client: Arc<dyn ProgramClient<T>>,
+        mint: Pubkey,
+        payer: Keypair,
+        decimals: u8,
+    ) -> Self {
+        let payer = Arc::new(payer);
+
+        // Helps us to interact with spl-token-programs
+        let token = Token::new(
+            program_client.clone(), // Program Client
+            &spl_token_2022::ID,    // SPL Token Program 2022 Publickey
+            &mint,                  // Mint Address
+            Some(decimals),         // Mint Decimal
+            payer.clone(),          // Payer
+        )
+        // Proof verification can exceed the default compute budget (e.g. the range proof of a
+        // transfer with fee), so every transaction requests the units its simulation consumed
+        .with_compute_unit_limit(ComputeUnitLimit::Simulated);
+
+        Self {
+            program_client,
+            token,
+            payer,
+            decimals,
+        }
+    }
+
+    /// Creates a client for an existing mint on top of any program client, reading its
+    /// decimals from the mint account.
+    pub async fn load_with_program_client(
+        program_client: Arc<dyn ProgramClient<T>>,
+        mint: Pubkey,
+        payer: Keypair,
+    ) -> Result<Self> {
+        let mint_account = program_client
+            .get_account(mint)
+            .await?
+            .ok_or_else(|| ConfidentialError::Invalid(format!("Mint {mint} not found")))?;
+        let mint_state = StateWithExtensionsOwned::<Mint>::unpack(mint_account.data)?;
+
+        Ok(Self::with_program_client(
+            program_client,
+            mint,
+            payer,
+            mint_state.base.decimals,
+        ))
+    }
+
+    pub fn program_client(&self) -> &Arc<dyn ProgramClient<T>> {
+        &self.program_client
+    }
+
+    pub fn token(&self) -> &Token<T> {
+        &self.token
+    }
+
+    pub fn payer(&self) -> &Keypair {
+        &self.payer
+    }
+
+    pub fn mint(&self) -> &Pubkey {
+        self.token.get_address()
+    }
+
+    pub fn decimals(&self) -> u8 {
+        self.decimals
+    }
+
+    /// Creates the mint with the ConfidentialTransferMint extension. The payer becomes
+    /// the mint authority and the confidential transfer authority.
+    ///
+    /// With `confidential_mint_burn`, the mint also gets the ConfidentialMintBurn extension;
+    /// its supply keys are derived from the payer keypair and the mint address.
+    ///
+    /// With `transfer_fee` (basis points, maximum fee in base units), the mint charges a
+    /// transfer fee. The payer becomes the withdraw withheld authority; the ElGamal keypair
+    /// the fees are withheld under is derived from the payer keypair and the mint address.
+    pub async fn create_mint(
+        &self,
+        mint_kp: &Keypair,
+        auditor_elgamal_pubkey: Option<PodElGamalPubkey>,
+        auto_approve_new_accounts: bool,
+        confidential_mint_burn: bool,
+        transfer_fee: Option<(u16, u64)>,
+    ) -> Result<()> {
+        if mint_kp.pubkey() != *self.mint() {
+            return Err(ConfidentialError::Invalid(format!(
+                "Mint keypair {} does not match the client mint {}",
+                mint_kp.pubkey(),
+                self.mint()
+            )));
+        }
+
+        let supply_keys = if confidential_mint_burn {
+            Some(derive_confidential_keys(&self.payer, self.mint())?)
+        } else {
+            None
+        };
+
+        let transfer_fee = match transfer_fee {
+            Some((transfer_fee_basis_points, maximum_fee)) => {
+                let withdraw_withheld_elgamal_kp =
+                    derive_withdraw_withheld_elgamal_keypair(&self.payer, self.mint())?;
+                Some(ConfidentialTransferFee {
+                    transfer_fee_basis_points,
+                    maximum_fee,
+                    withdraw_withheld_authority_elgamal_pubkey: (*withdraw_withheld_elgamal_kp
+                        .pubkey())
+                    .into(),
+                })
+            }
+            None => None,
+        };
+
+        create_confidential_mint(
+            &self.payer.pubkey(),
+            &[mint_kp, &self.payer],
+            &self.token,
+            auditor_elgamal_pubkey,
+            auto_approve_new_accounts,
+            supply_keys
+                .as_ref()
+                .map(|(elgamal_kp, aes_kp)| (elgamal_kp, aes_kp)),
+            transfer_fee,
+        )
+        .await
+    }
+
+    /// Creates and configures a confidential token account for `owner` that accepts up to
+    /// `maximum_pending_balance_credit_counter` credits before its pending balance must be applied.
+    /// With `associated`, the account is created at the owner's associated token address.
+    pub async fn create_account(
+        &self,
+        owner: &Keypair,
+        maximum_pending_balance_credit_counter: u64,
+        associated: bool,
+    ) -> Result<ConfTokenAccountRes> {
+        create_confidential_token_acc(
+            owner,
+            self.mint(),
+            maximum_pending_balance_credit_counter,
+            associated,
+            self.program_client.as_ref(),
+            &self.token,
+        )
+        .await
+    }
+
+    /// Recreates the ElGamal keypair and AE key of a token account from `owner` and checks
+    /// them against the ElGamal pubkey stored on-chain.
+    pub async fn recover_keys(
+        &self,
+        owner: &Keypair,
+        token_account: &Pubkey,
+    ) -> Result<ConfTokenAccountRes> {
+        recover_confidential_keys(&self.token, owner, token_account).await
+    }
+
+    /// Rotates the ElGamal key of a token account: moves its balances to a new token account
+    /// configured with a new ElGamal key, then empties and closes the old account. On a fee
+    /// mint it is refused unless `allow_transfer_fee` accepts paying the fee on the balances.
+    pub async fn rotate_account(
+        &self,
+        owner: &Keypair,
+        token_account: &Pubkey,
+        allow_transfer_fee: bool,
+    ) -> Result<ConfTokenAccountRes> {
+        let (elgamal_kp, aes_kp) = derive_confidential_keys(owner, token_account)?;
+        rotate_elgamal_key(
+            &self.token,
+            self.program_client.as_ref(),
+            owner,
+            &elgamal_kp,
+            &aes_kp,
+            token_account,
+            allow_transfer_fee,
+        )
+        .await
+    }
+
+    /// Approves a token account on a mint that requires manual approval.
+    pub async fn approve_account(&self, authority: &Keypair, token_account: &Pubkey) -> Result<()> {
+        approve_account(token_account, authority, &self.token).await
+    }
+
+    /// Mints public tokens to a token account.
+    pub async fn mint_to(
+        &self,
+        authority: &Keypair,
+        token_account: &Pubkey,
+        amount: u64,
+    ) -> Result<()> {
+        self.token
+            .mint_to(
+                token_account,       // Destination
+                &authority.pubkey(), // Mint authority
+                amount,              // Minting tokens (in base units)
+                &[authority],        // Signers
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Mints tokens straight into the pending confidential balance of a token account.
+    /// Requires a mint created with `confidential_mint_burn`; `authority` must be the
+    /// mint authority that created it.
+    pub async fn confidential_mint_to(
+        &self,
+        authority: &Keypair,
+        token_account: &Pubkey,
+        amount: u64,
+    ) -> Result<()> {
+        let (supply_elgamal_kp, supply_aes_kp) = derive_confidential_keys(authority, self.mint())?;
+        confidential_mint_to(
+            &self.token,
+            authority,
+            &supply_elgamal_kp,
+            &supply_aes_kp,
+            token_account,
+            amount,
+        )
+        .await
+    }
+
+    /// Burns tokens from the available confidential balance of a token account.
+    /// Requires a mint created with `confidential_mint_burn`.
+    pub async fn burn(&self, owner: &Keypair, token_account: &Pubkey, amount: u64) -> Result<()> {
+        let (elgamal_kp, aes_kp) = derive_confidential_keys(owner, token_account)?;
+        confidential_burn(
+            &self.token,
+            owner,
+            &elgamal_kp,
+            &aes_kp,
+            token_account,
+            amount,
+        )
+        .await
+    }
+
+    /// Deposits public tokens into the confidential balance and applies them.
+    pub async fn deposit(
+        &self,
+        owner: &Keypair,
+        token_account: &Pubkey,
+        amount: u64,
+    ) -> Result<()> {
+        let (elgamal_kp, aes_kp) = derive_confidential_keys(owner, token_account)?;
+        deposite_token_to_confidential(
+            token_account,
+            owner,
+            &self.token,
+            &elgamal_kp,
+            &aes_kp,
+            amount,
+        )
+        .await
+    }
+
+    /// Applies the pending confidential balance to the available balance.
+    pub async fn apply_pending(&self, owner: &Keypair, token_account: &Pubkey) -> Result<()> {
+        let (elgamal_kp, aes_kp) = derive_confidential_keys(owner, token_account)?;
+        apply_pending(&self.token, owner, &elgamal_kp, &aes_kp, token_account).await
+    }
+
+    /// Applies the pending balance if the pending balance credit counter is within `headroom`
+    /// credits of its maximum. Returns whether it was applied.
+    pub async fn apply_pending_if_near_limit(
+        &self,
+        owner: &Keypair,
+        token_account: &Pubkey,
+        headroom: u64,
+    ) -> Result<bool> {
+        let (elgamal_kp, aes_kp) = derive_confidential_keys(owner, token_account)?;
+        apply_pending_if_near_limit(
+            &self.token,
+            owner,
+            &elgamal_kp,
+            &aes_kp,
+            token_account,
+            headroom,
+        )
+        .await
+    }
+
+    /// Reads the pending balance credit counter every `poll_interval` (and on every account
+    /// change when `rpc_config` has a WebSocket endpoint) and applies the pending balance
+    /// before the limit is reached. Runs until an error occurs.
+    pub async fn watch_pending(
+        &self,
+        owner: &Keypair,
+        token_account: &Pubkey,
+        headroom: u64,
+        poll_interval: Duration,
+        rpc_config: Option<&RpcConfig>,
+    ) -> Result<()> {
+        let (elgamal_kp, aes_kp) = derive_confidential_keys(owner, token_account)?;
+        watch_pending_balance(
+            &self.token,
+            owner,
+            &elgamal_kp,
+            &aes_kp,
+            token_account,
+            headroom,
+            poll_interval,
+            rpc_config,
+        )
+        .await
+    }
+
+    /// Transfers tokens confidentially to a recipient identified by its token account.
+    pub async fn transfer(
+        &self,
+        owner: &Keypair,
+        token_account: &Pubkey,
+        recipient_token_account: &Pubkey,
+        amount: u64,
+    ) -> Result<()> {
+        let (elgamal_kp, aes_kp) = derive_confidential_keys(owner, token_account)?;
+        transfer_tokens(
+            amount,
+            &self.token,
+            token_account,
+            &elgamal_kp,
+            &aes_kp,
+            owner,
+            recipient_token_account,
+        )
+        .await
+    }
+
+    /// Transfers tokens confidentially in a single transaction, with the equality and validity
+    /// proofs verified inline where they fit. Falls back to [`ConfidentialClient::transfer`]
+    /// when even the equality proof does not fit; returns the number of inline proofs.
+    pub async fn transfer_with_inline_proofs(
+        &self,
+        owner: &Keypair,
+        token_account: &Pubkey,
+        recipient_token_account: &Pubkey,
+        amount: u64,
+    ) -> Result<usize> {
+        let (elgamal_kp, aes_kp) = derive_confidential_keys(owner, token_account)?;
+        transfer_tokens_with_inline_proofs(
+            amount,
+            &self.token,
+            token_account,
+            &elgamal_kp,
+            &aes_kp,
+            owner,
+            recipient_token_account,
+        )
+        .await
+    }
+
+    /// Withdraws tokens from the confidential balance back to the public balance.
+    pub async fn withdraw(
+        &self,
+        owner: &Keypair,
+        token_account: &Pubkey,
+        amount: u64,
+    ) -> Result<()> {
+        let (elgamal_kp, aes_kp) = derive_confidential_keys(owner, token_account)?;
+        withdraw_tokens(
+            token_account,
+            &elgamal_kp,
+            &aes_kp,
+            amount,
+            &self.token,
+            owner,
+        )
+        .await
+    }
+
+    /// Allows or refuses incoming confidential transfers to a token account.
+    pub async fn set_confidential_credits(
+        &self,
+        owner: &Keypair,
+        token_account: &Pubkey,
+        enabled: bool,
+    ) -> Result<()> {
+        set_confidential_credits(&self.token, owner, token_account, enabled).await
+    }
+
+    /// Allows or refuses incoming public transfers to a token account.
+    pub async fn set_non_confidential_credits(
+        &self,
+        owner: &Keypair,
+        token_account: &Pubkey,
+        enabled: bool,
+    ) -> Result<()> {
+        set_non_confidential_credits(&self.token, owner, token_account, enabled).await
+    }
+
+    /// Empties a confidential token account and closes it, returning its rent to `owner`.
+    /// The pending balance must be applied and all balances must be zero.
+    pub async fn close_account(&self, owner: &Keypair, token_account: &Pubkey) -> Result<()> {
+        let (elgamal_kp, aes_kp) = derive_confidential_keys(owner, token_account)?;
+        empty_account(&self.token, owner, &elgamal_kp, &aes_kp, token_account).await
+    }
+
+    /// Moves the confidential transfer fees withheld in token accounts to the mint.
+    pub async fn harvest_fees(&self, source_token_accounts: &[Pubkey]) -> Result<()> {
+        harvest_withheld_fees(&self.token, source_token_accounts).await
+    }
+
+    /// Withdraws the confidential transfer fees withheld in the mint to a fee account
+    /// owned by `authority`, the withdraw withheld authority that created the mint.
+    /// Returns the withdrawn amount.
+    pub async fn withdraw_fees(&self, authority: &Keypair, fee_account: &Pubkey) -> Result<u64> {
+        let withdraw_withheld_elgamal_kp =
+            derive_withdraw_withheld_elgamal_keypair(authority, self.mint())?;
+        let (_, fee_account_aes_kp) = derive_confidential_keys(authority, fee_account)?;
+        withdraw_withheld_fees(
+            &self.token,
+            authority,
+            &withdraw_withheld_elgamal_kp,
+            fee_account,
+            &fee_account_aes_kp,
+        )
+        .await
+    }
+
+    /// Returns the public balance of a token account.
+    pub async fn public_balance(&self, token_account: &Pubkey) -> Result<u64> {
+        let account_info = self.token.get_account_info(token_account).await?;
+        Ok(account_info.base.amount)
+    }
+
+    /// Decrypts the pending and available confidential balances of a token account.
+    pub async fn confidential_balances(
+        &self,
+        owner: &Keypair,
+        token_account: &Pubkey,
+    ) -> Result<ConfidentialBalances> {
+        let (elgamal_kp, aes_kp) = derive_confidential_keys(owner, token_account)?;
+        let account_info = self.token.get_account_info(token_account).await?;
+        let extension = account_info.get_extension::<ConfidentialTransferAccount>()?;
+
+        decrypt_confidential_balances(extension, &elgamal_kp, &aes_kp)
+    }
+}
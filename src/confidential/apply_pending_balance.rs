@@ -1,30 +1,39 @@
-use anyhow::{Ok, Result};
-use solana_sdk::{signature::Keypair, signer::Signer};
+use futures_util::StreamExt;
+use solana_account_decoder_client_types::UiAccountEncoding;
+use solana_client::{nonblocking::pubsub_client::PubsubClient, rpc_config::RpcAccountInfoConfig};
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
 use spl_token_client::{
-    client::ProgramRpcClientSendTransaction,
-    spl_token_2022::solana_zk_sdk::encryption::{auth_encryption::AeKey, elgamal::ElGamalKeypair},
+    spl_token_2022::{
+        extension::{confidential_transfer::ConfidentialTransferAccount, BaseStateWithExtensions},
+        solana_zk_sdk::encryption::{auth_encryption::AeKey, elgamal::ElGamalKeypair},
+    },
     token::Token,
 };
+use std::time::Duration;
 
-use crate::helper::handle_token_response;
+use crate::{
+    config::RpcConfig,
+    error::{ConfidentialError, Result},
+    helper::{handle_token_response, ConfidentialSender},
+};
 
 /// Applies the pending confidential balance to the available balance for a token account.
 /// This is required after a confidential transfer or deposit to make the tokens usable.
-pub async fn apply_pending(
-    token: &Token<ProgramRpcClientSendTransaction>,
-    payer: &Keypair,                // The account paying for the transaction fees
-    elgamal_kp: &ElGamalKeypair,    // ElGamal keypair for decrypting the confidential balance
-    aes_kp: &AeKey,                 // AE key for decrypting the confidential balance
-    token_account_kp: &Keypair,     // The confidential token account
+pub async fn apply_pending<T: ConfidentialSender>(
+    token: &Token<T>,
+    payer: &Keypair,             // The account paying for the transaction fees
+    elgamal_kp: &ElGamalKeypair, // ElGamal keypair for decrypting the confidential balance
+    aes_kp: &AeKey,              // AE key for decrypting the confidential balance
+    token_account: &Pubkey,      // The confidential token account
 ) -> Result<()> {
     println!("\n======== Converting Pending Balance to Available Balance ========");
-    println!("Account: {}", token_account_kp.pubkey());
+    println!("Account: {}", token_account);
     println!("Authority: {}", payer.pubkey());
     println!("\nStep 1: Decrypting pending balance using account's cryptographic keys...");
-    
+
     let apply_sig = token
         .confidential_transfer_apply_pending_balance(
-            &token_account_kp.pubkey(),
+            token_account,
             &payer.pubkey(),
             None,
             elgamal_kp.secret(),
@@ -37,6 +46,111 @@ pub async fn apply_pending(
 
     println!("\nStep 2: Converting decrypted pending balance to available balance...");
     println!("✓ Successfully moved pending balance to available balance");
-    println!("Note: The available balance is encrypted and can only be viewed by the account owner");
+    println!(
+        "Note: The available balance is encrypted and can only be viewed by the account owner"
+    );
     Ok(())
 }
+
+/// Applies the pending balance once the pending balance credit counter of a token account
+/// comes within `headroom` credits of its maximum. Does nothing if there is nothing pending.
+///
+/// # Returns
+/// * `bool` - Whether the pending balance was applied.
+pub async fn apply_pending_if_near_limit<T: ConfidentialSender>(
+    token: &Token<T>,
+    payer: &Keypair,             // The account paying for the transaction fees
+    elgamal_kp: &ElGamalKeypair, // ElGamal keypair for decrypting the confidential balance
+    aes_kp: &AeKey,              // AE key for decrypting the confidential balance
+    token_account: &Pubkey,      // The confidential token account
+    headroom: u64,               // Credits left before the limit at which to apply
+) -> Result<bool> {
+    let account = token.get_account_info(token_account).await?;
+    let extension = account.get_extension::<ConfidentialTransferAccount>()?;
+    let credits = u64::from(extension.pending_balance_credit_counter);
+    let maximum = u64::from(extension.maximum_pending_balance_credit_counter);
+
+    if credits == 0 || credits < maximum.saturating_sub(headroom) {
+        return Ok(false);
+    }
+
+    println!(
+        "⚠️ Pending balance credit counter at {}/{}, applying pending balance...",
+        credits, maximum
+    );
+    apply_pending(token, payer, elgamal_kp, aes_kp, token_account).await?;
+
+    Ok(true)
+}
+
+/// Watches the pending balance credit counter of a token account and applies the pending
+/// balance before the limit is reached, so incoming transfers are never rejected.
+/// Runs until an error occurs.
+///
+/// # Arguments
+/// * `token` - The SPL Token client.
+/// * `payer` - The owner of the token account.
+/// * `elgamal_kp` - ElGamal keypair of the token account.
+/// * `aes_kp` - AE key of the token account.
+/// * `token_account` - Confidential token account to watch.
+/// * `headroom` - Credits left before the limit at which the pending balance is applied.
+/// * `poll_interval` - Maximum time between two reads of the counter.
+/// * `rpc_config` - With a WebSocket endpoint, the counter is also read on every change of
+///   the account instead of only every `poll_interval`.
+#[allow(clippy::too_many_arguments)]
+pub async fn watch_pending_balance<T: ConfidentialSender>(
+    token: &Token<T>,
+    payer: &Keypair,
+    elgamal_kp: &ElGamalKeypair,
+    aes_kp: &AeKey,
+    token_account: &Pubkey,
+    headroom: u64,
+    poll_interval: Duration,
+    rpc_config: Option<&RpcConfig>,
+) -> Result<()> {
+    println!("\n======== Watching Pending Balance Credit Counter ========");
+    println!("Account: {}", token_account);
+    println!(
+        "Applying when {} or fewer credits are left, checking every {:?}",
+        headroom, poll_interval
+    );
+
+    let pubsub_client = match rpc_config {
+        Some(rpc_config) => {
+            println!("Subscribing to account changes at {}", rpc_config.ws_url);
+            Some(PubsubClient::new(&rpc_config.ws_url).await?)
+        }
+        None => None,
+    };
+    let mut account_changes = match (&pubsub_client, rpc_config) {
+        (Some(pubsub_client), Some(rpc_config)) => {
+            let config = RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                commitment: Some(rpc_config.commitment),
+                ..RpcAccountInfoConfig::default()
+            };
+            let (account_changes, _unsubscribe) = pubsub_client
+                .account_subscribe(token_account, Some(config))
+                .await?;
+            Some(account_changes)
+        }
+        _ => None,
+    };
+
+    loop {
+        apply_pending_if_near_limit(token, payer, elgamal_kp, aes_kp, token_account, headroom)
+            .await?;
+        match account_changes.as_mut() {
+            // Wakes on the next change of the account, or after the interval at the latest
+            Some(account_changes) => {
+                if let Ok(None) = tokio::time::timeout(poll_interval, account_changes.next()).await
+                {
+                    return Err(ConfidentialError::Rpc(String::from(
+                        "Account subscription closed",
+                    )));
+                }
+            }
+            None => tokio::time::sleep(poll_interval).await,
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+use spl_token_client::{
+    spl_token_2022::extension::{
+        confidential_transfer::ConfidentialTransferAccount, BaseStateWithExtensions,
+    },
+    token::Token,
+};
+
+use crate::{
+    error::Result,
+    helper::{handle_token_response, ConfidentialSender},
+};
+
+/// Approves a confidential token account on a mint with `auto_approve_new_accounts` disabled.
+///
+/// # Arguments
+/// * `token_account` - The confidential token account to approve.
+/// * `authority` - The confidential transfer authority of the mint.
+/// * `token` - The SPL Token client.
+///
+/// # Flow
+/// 1. Skips accounts that are already approved.
+/// 2. Sends the `ApproveAccount` instruction signed by the confidential transfer authority.
+pub async fn approve_account<T: ConfidentialSender>(
+    token_account: &Pubkey,
+    authority: &Keypair,
+    token: &Token<T>,
+) -> Result<()> {
+    println!("\n======== Approving Confidential Token Account ========");
+    println!("- Token Account: {}", token_account);
+    println!("- Confidential Transfer Authority: {}", authority.pubkey());
+
+    if is_account_approved(token_account, token).await? {
+        println!("✓ Account is already approved");
+        return Ok(());
+    }
+
+    let approve_sig = token
+        .confidential_transfer_approve_account(token_account, &authority.pubkey(), &[authority])
+        .await?;
+
+    handle_token_response(&approve_sig, String::from("approving confidential account")).await?;
+    println!("✓ Account approved for confidential transfers");
+
+    Ok(())
+}
+
+/// Returns whether a confidential token account has been approved for confidential transfers.
+pub async fn is_account_approved<T: ConfidentialSender>(
+    token_account: &Pubkey,
+    token: &Token<T>,
+) -> Result<bool> {
+    let account_info = token.get_account_info(token_account).await?;
+    let extension = account_info.get_extension::<ConfidentialTransferAccount>()?;
+
+    Ok(bool::from(extension.approved))
+}
@@ -0,0 +1,249 @@
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{pubkey::Pubkey, signature::Signature, transaction::VersionedTransaction};
+use solana_transaction_status_client_types::UiTransactionEncoding;
+use spl_token_client::spl_token_2022::{
+    self,
+    extension::{
+        confidential_transfer::{
+            instruction::{
+                ConfidentialTransferInstruction, TransferInstructionData,
+                TransferWithFeeInstructionData,
+            },
+            ConfidentialTransferMint,
+        },
+        BaseStateWithExtensions, StateWithExtensionsOwned,
+    },
+    instruction::{decode_instruction_data, decode_instruction_type, TokenInstruction},
+    solana_zk_sdk::encryption::{
+        elgamal::{ElGamalCiphertext, ElGamalKeypair},
+        pod::elgamal::{PodElGamalCiphertext, PodElGamalPubkey},
+    },
+    state::Mint,
+};
+use spl_token_confidential_transfer_proof_generation::TRANSFER_AMOUNT_LO_BITS;
+
+use crate::error::{ConfidentialError, Result};
+
+/// A confidential transfer found in a transaction, with the amount still encrypted for the
+/// mint's auditor.
+pub struct EncryptedTransfer {
+    pub source: Pubkey,                              // Source token account
+    pub mint: Pubkey,        // Mint whose auditor the amount is encrypted for
+    pub destination: Pubkey, // Destination token account
+    pub with_fee: bool,      // Transfer with fee, the amount includes the withheld fee
+    pub auditor_ciphertext_lo: PodElGamalCiphertext, // Low 16 bits of the amount
+    pub auditor_ciphertext_hi: PodElGamalCiphertext, // High 32 bits of the amount
+}
+
+impl EncryptedTransfer {
+    /// Decrypts the transfer amount with the auditor's ElGamal keypair. The keypair must be
+    /// the mint's auditor, any other key fails only after brute forcing a discrete log.
+    pub fn decrypt_amount(&self, auditor_elgamal_kp: &ElGamalKeypair) -> Result<u64> {
+        decrypt_auditor_amount(
+            auditor_elgamal_kp,
+            self.auditor_ciphertext_lo,
+            self.auditor_ciphertext_hi,
+        )
+    }
+}
+
+/// A confidential transfer found in a transaction, with the amount decrypted by the auditor.
+pub struct AuditedTransfer {
+    pub source: Pubkey,      // Source token account
+    pub destination: Pubkey, // Destination token account
+    pub amount: u64,         // Transfer amount in base units
+    pub with_fee: bool,      // Transfer with fee, the amount includes the withheld fee
+}
+
+/// Decrypts the amounts of all confidential transfers in a transaction using the auditor's
+/// ElGamal keypair.
+///
+/// # Arguments
+/// * `rpc_client` - The Solana RPC client.
+/// * `signature` - Signature of the transaction containing the confidential transfer(s).
+/// * `auditor_elgamal_kp` - The auditor's ElGamal keypair configured on the mint.
+///
+/// # Flow
+/// 1. Fetches the transaction, fails if it did not succeed, and finds the Token-2022
+///    confidential `Transfer` and `TransferWithFee` instructions.
+/// 2. Checks that the auditor keypair matches the auditor configured on the mint.
+/// 3. Reads the auditor ciphertexts (low 16 bits and high 32 bits of the amount).
+/// 4. Decrypts both parts with the auditor's ElGamal secret key and combines them.
+pub async fn audit_transfer(
+    rpc_client: &RpcClient,
+    signature: &Signature,
+    auditor_elgamal_kp: &ElGamalKeypair,
+) -> Result<Vec<AuditedTransfer>> {
+    println!("\n======== Auditing Confidential Transfer ========");
+    println!("Transaction: {}", signature);
+
+    let transaction = rpc_client
+        .get_transaction(signature, UiTransactionEncoding::Base64)
+        .await?
+        .transaction;
+
+    // A failed transaction moved no tokens, its amounts must not be reported as transfers
+    if let Some(error) = transaction.meta.and_then(|meta| meta.err) {
+        return Err(ConfidentialError::Invalid(format!(
+            "Transaction {signature} failed ({error}), its transfers were not executed"
+        )));
+    }
+
+    let transaction = transaction.transaction.decode().ok_or_else(|| {
+        ConfidentialError::Invalid(format!("Failed to decode transaction {signature}"))
+    })?;
+
+    let mut transfers = Vec::new();
+    for transfer in find_confidential_transfers(&transaction)? {
+        // Decrypting with a key that is not the mint's auditor would brute force a discrete log
+        check_mint_auditor(rpc_client, &transfer.mint, auditor_elgamal_kp).await?;
+        let amount = transfer.decrypt_amount(auditor_elgamal_kp)?;
+
+        println!("\nConfidential Transfer:");
+        println!("- From: {}", transfer.source);
+        println!("- To: {}", transfer.destination);
+        println!("- Amount: {} (base units)", amount);
+        if transfer.with_fee {
+            println!("- Fee: withheld from the amount in the destination account");
+        }
+
+        transfers.push(AuditedTransfer {
+            source: transfer.source,
+            destination: transfer.destination,
+            amount,
+            with_fee: transfer.with_fee,
+        });
+    }
+
+    if transfers.is_empty() {
+        return Err(ConfidentialError::Invalid(format!(
+            "No confidential transfer instruction found in transaction {signature}"
+        )));
+    }
+
+    Ok(transfers)
+}
+
+/// Finds the Token-2022 confidential `Transfer` and `TransferWithFee` instructions of a
+/// transaction and reads their accounts and auditor ciphertexts, without fetching anything.
+pub fn find_confidential_transfers(
+    transaction: &VersionedTransaction,
+) -> Result<Vec<EncryptedTransfer>> {
+    let account_keys = transaction.message.static_account_keys();
+    let account = |index: u8| -> Result<Pubkey> {
+        account_keys.get(index as usize).copied().ok_or_else(|| {
+            ConfidentialError::Invalid(String::from(
+                "Transaction uses address lookup tables, which are not supported",
+            ))
+        })
+    };
+
+    let mut transfers = Vec::new();
+    for instruction in transaction.message.instructions() {
+        if account(instruction.program_id_index)? != spl_token_2022::id() {
+            continue;
+        }
+
+        // Only confidential transfer `Transfer` and `TransferWithFee` instructions carry
+        // auditor ciphertexts
+        let data = &instruction.data;
+        if !matches!(
+            TokenInstruction::unpack(data),
+            Ok(TokenInstruction::ConfidentialTransferExtension)
+        ) {
+            continue;
+        }
+        let (with_fee, auditor_ciphertext_lo, auditor_ciphertext_hi) =
+            match decode_instruction_type(&data[1..]) {
+                Ok(ConfidentialTransferInstruction::Transfer) => {
+                    let transfer_data =
+                        decode_instruction_data::<TransferInstructionData>(&data[1..])?;
+                    (
+                        false,
+                        transfer_data.transfer_amount_auditor_ciphertext_lo,
+                        transfer_data.transfer_amount_auditor_ciphertext_hi,
+                    )
+                }
+                Ok(ConfidentialTransferInstruction::TransferWithFee) => {
+                    let transfer_data =
+                        decode_instruction_data::<TransferWithFeeInstructionData>(&data[1..])?;
+                    (
+                        true,
+                        transfer_data.transfer_amount_auditor_ciphertext_lo,
+                        transfer_data.transfer_amount_auditor_ciphertext_hi,
+                    )
+                }
+                _ => continue,
+            };
+
+        // Both transfer instructions start with [source, mint, destination, ...]
+        let instruction_account = |position: usize| -> Result<Pubkey> {
+            let index = instruction.accounts.get(position).ok_or_else(|| {
+                ConfidentialError::Invalid(String::from(
+                    "Confidential transfer instruction is missing its token accounts",
+                ))
+            })?;
+            account(*index)
+        };
+
+        transfers.push(EncryptedTransfer {
+            source: instruction_account(0)?,
+            mint: instruction_account(1)?,
+            destination: instruction_account(2)?,
+            with_fee,
+            auditor_ciphertext_lo,
+            auditor_ciphertext_hi,
+        });
+    }
+
+    Ok(transfers)
+}
+
+/// Fails unless the auditor keypair matches the auditor ElGamal pubkey configured on the mint.
+async fn check_mint_auditor(
+    rpc_client: &RpcClient,
+    mint: &Pubkey,
+    auditor_elgamal_kp: &ElGamalKeypair,
+) -> Result<()> {
+    let mint_data = rpc_client.get_account_data(mint).await?;
+    let mint_state = StateWithExtensionsOwned::<Mint>::unpack(mint_data)?;
+    let extension = mint_state.get_extension::<ConfidentialTransferMint>()?;
+
+    if !extension
+        .auditor_elgamal_pubkey
+        .equals(&PodElGamalPubkey::from(*auditor_elgamal_kp.pubkey()))
+    {
+        return Err(ConfidentialError::AuditorMismatch);
+    }
+
+    Ok(())
+}
+
+/// Decrypts a transfer amount split into a low and a high ciphertext with the auditor's key.
+fn decrypt_auditor_amount(
+    auditor_elgamal_kp: &ElGamalKeypair,
+    ciphertext_lo: PodElGamalCiphertext,
+    ciphertext_hi: PodElGamalCiphertext,
+) -> Result<u64> {
+    let decrypt = |ciphertext: PodElGamalCiphertext| -> Result<u64> {
+        let ciphertext = ElGamalCiphertext::try_from(ciphertext).map_err(|e| {
+            ConfidentialError::Decryption(format!("Malformed auditor ciphertext: {e}"))
+        })?;
+        auditor_elgamal_kp
+            .secret()
+            .decrypt_u32(&ciphertext)
+            .ok_or_else(|| {
+                ConfidentialError::Decryption(String::from(
+                    "Failed to decrypt the transfer amount with the auditor key",
+                ))
+            })
+    };
+
+    let amount_lo = decrypt(ciphertext_lo)?;
+    let amount_hi = decrypt(ciphertext_hi)?;
+
+    amount_hi
+        .checked_shl(TRANSFER_AMOUNT_LO_BITS as u32)
+        .and_then(|hi| hi.checked_add(amount_lo))
+        .ok_or_else(|| ConfidentialError::Decryption(String::from("Transfer amount overflow")))
+}
@@ -0,0 +1,109 @@
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+use spl_token_client::{
+    spl_token_2022::extension::{
+        confidential_transfer::ConfidentialTransferAccount, BaseStateWithExtensions,
+    },
+    token::Token,
+};
+
+use crate::{
+    error::Result,
+    helper::{handle_token_response, ConfidentialSender},
+};
+
+/// Allows or refuses incoming confidential transfers to a token account.
+///
+/// # Arguments
+/// * `token` - The SPL Token client.
+/// * `owner` - The owner of the token account.
+/// * `token_account` - Confidential token account to update.
+/// * `enabled` - Whether confidential transfers to the account are accepted.
+pub async fn set_confidential_credits<T: ConfidentialSender>(
+    token: &Token<T>,
+    owner: &Keypair,
+    token_account: &Pubkey,
+    enabled: bool,
+) -> Result<()> {
+    println!("\n======== Updating Confidential Credits ========");
+    println!("Account: {}", token_account);
+
+    let sig = if enabled {
+        token
+            .confidential_transfer_enable_confidential_credits(
+                token_account,
+                &owner.pubkey(),
+                &[owner],
+            )
+            .await?
+    } else {
+        token
+            .confidential_transfer_disable_confidential_credits(
+                token_account,
+                &owner.pubkey(),
+                &[owner],
+            )
+            .await?
+    };
+    handle_token_response(&sig, String::from("updating confidential credits")).await?;
+
+    print_credit_flags(token, token_account).await
+}
+
+/// Allows or refuses incoming public (non-confidential) transfers to a token account,
+/// e.g. to keep a treasury account fully confidential.
+///
+/// # Arguments
+/// * `token` - The SPL Token client.
+/// * `owner` - The owner of the token account.
+/// * `token_account` - Confidential token account to update.
+/// * `enabled` - Whether public transfers to the account are accepted.
+pub async fn set_non_confidential_credits<T: ConfidentialSender>(
+    token: &Token<T>,
+    owner: &Keypair,
+    token_account: &Pubkey,
+    enabled: bool,
+) -> Result<()> {
+    println!("\n======== Updating Non-Confidential Credits ========");
+    println!("Account: {}", token_account);
+
+    let sig = if enabled {
+        token
+            .confidential_transfer_enable_non_confidential_credits(
+                token_account,
+                &owner.pubkey(),
+                &[owner],
+            )
+            .await?
+    } else {
+        token
+            .confidential_transfer_disable_non_confidential_credits(
+                token_account,
+                &owner.pubkey(),
+                &[owner],
+            )
+            .await?
+    };
+    handle_token_response(&sig, String::from("updating non-confidential credits")).await?;
+
+    print_credit_flags(token, token_account).await
+}
+
+/// Prints the credit flags of a confidential token account as stored on-chain.
+async fn print_credit_flags<T: ConfidentialSender>(
+    token: &Token<T>,
+    token_account: &Pubkey,
+) -> Result<()> {
+    let account = token.get_account_info(token_account).await?;
+    let extension = account.get_extension::<ConfidentialTransferAccount>()?;
+
+    println!(
+        "- Confidential credits allowed: {}",
+        bool::from(extension.allow_confidential_credits)
+    );
+    println!(
+        "- Non-confidential credits allowed: {}",
+        bool::from(extension.allow_non_confidential_credits)
+    );
+
+    Ok(())
+}
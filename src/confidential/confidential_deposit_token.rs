@@ -1,30 +1,32 @@
-use anyhow::{Ok, Result};
-use solana_sdk::{signature::Keypair, signer::Signer};
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
 use spl_token_client::{
-    client::ProgramRpcClientSendTransaction,
     spl_token_2022::solana_zk_sdk::encryption::{auth_encryption::AeKey, elgamal::ElGamalKeypair},
     token::Token,
 };
 
 use super::apply_pending;
-use crate::helper::handle_token_response;
+use crate::{
+    error::{ConfidentialError, Result},
+    helper::{format_token_amount, handle_token_response, ConfidentialSender},
+};
 
 /// Deposits tokens into a confidential account.
 ///
 /// # Arguments
-/// * `token_account_kp` - The confidential token account keypair.
+/// * `token_account` - The confidential token account.
 /// * `payer` - The keypair paying for the transaction.
 /// * `token` - The SPL Token client.
 /// * `elgamal_kp` - ElGamal keypair for confidential encryption.
 /// * `aes_kp` - AE key for confidential encryption.
+/// * `amount` - Amount to deposit in base units (the mint's decimals are read from the mint).
 ///
 /// # Flow
 /// 1. Deposit tokens to the 'pending' confidential balance.
 /// 2. Apply the 'pending' balance to make it available for spending.
-pub async fn deposite_token_to_confidential(
-    token_account_kp: &Keypair,
+pub async fn deposite_token_to_confidential<T: ConfidentialSender>(
+    token_account: &Pubkey,
     payer: &Keypair,
-    token: &Token<ProgramRpcClientSendTransaction>,
+    token: &Token<T>,
     elgamal_kp: &ElGamalKeypair,
     aes_kp: &AeKey,
 
@@ -36,16 +38,27 @@ pub async fn deposite_token_to_confidential(
     println!("2. Apply pending to 'available' balance");
 
     // Step 1: Deposit tokens to the 'pending' confidential balance.
-    println!("\nStep 1: Depositing 100 tokens to pending balance...");
-    println!("- Token Account: {}", token_account_kp.pubkey());
-    println!("- Amount: {} tokens ", amount);
+    println!("\nStep 1: Depositing tokens to pending balance...");
+    println!("- Token Account: {}", token_account);
+
+    // Read the decimals from the mint, the deposit instruction checks them
+    let decimals = token.get_mint_info().await?.base.decimals;
+    println!(
+        "- Amount: {} tokens ",
+        format_token_amount(amount, decimals)
+    );
+
+    // On-chain, a deposit over the public balance only fails with an overflow
+    if token.get_account_info(token_account).await?.base.amount < amount {
+        return Err(ConfidentialError::InsufficientPublicBalance);
+    }
 
     let deposit_sig = token
         .confidential_transfer_deposit(
-            &token_account_kp.pubkey(),
+            token_account,
             &payer.pubkey(),
-            amount * 10u64.pow(6), // Amount to deposit (adjust for decimals)
-            6,                     // Token decimals
+            amount,   // Amount to deposit (in base units)
+            decimals, // Token decimals
             &[payer],
         )
         .await?;
@@ -54,8 +67,8 @@ pub async fn deposite_token_to_confidential(
 
     // Step 2: Apply the 'pending' balance to make it available for spending.
     println!("\nStep 2: Converting pending balance to available balance...");
-    println!("- Token Account: {}", token_account_kp.pubkey());
-    apply_pending(&token, &payer, &elgamal_kp, &aes_kp, &token_account_kp).await?;
+    println!("- Token Account: {}", token_account);
+    apply_pending(token, payer, elgamal_kp, aes_kp, token_account).await?;
     println!("✓ Successfully converted pending balance to available balance");
 
     Ok(())
@@ -0,0 +1,105 @@
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+use spl_token_client::{
+    spl_token_2022::{
+        extension::{
+            confidential_transfer::ConfidentialTransferAccount,
+            confidential_transfer_fee::ConfidentialTransferFeeAmount, BaseStateWithExtensions,
+        },
+        solana_zk_sdk::encryption::{auth_encryption::AeKey, elgamal::ElGamalKeypair},
+    },
+    token::Token,
+};
+
+use crate::{
+    error::{ConfidentialError, Result},
+    helper::{
+        decrypt_confidential_balances, format_token_amount, handle_token_response,
+        ConfidentialSender,
+    },
+};
+
+/// Empties a confidential token account and closes it, returning its rent to the owner.
+///
+/// # Arguments
+/// * `token` - The SPL Token client.
+/// * `owner` - The owner of the token account (receives the lamports).
+/// * `elgamal_kp` - ElGamal keypair of the token account.
+/// * `aes_kp` - AE key of the token account.
+/// * `token_account` - Confidential token account to close.
+///
+/// # Flow
+/// 1. Checks that the pending balance has been applied and that the available, public and
+///    withheld fee balances are zero.
+/// 2. Generates a zero-balance proof for the available balance ciphertext and empties the
+///    confidential extension with it.
+/// 3. Closes the token account and sends its lamports to the owner.
+pub async fn empty_account<T: ConfidentialSender>(
+    token: &Token<T>,
+    owner: &Keypair,
+    elgamal_kp: &ElGamalKeypair,
+    aes_kp: &AeKey,
+    token_account: &Pubkey,
+) -> Result<()> {
+    let decimals = token.get_mint_info().await?.base.decimals;
+    let account = token.get_account_info(token_account).await?;
+    let extension = account.get_extension::<ConfidentialTransferAccount>()?;
+
+    println!("\n======== Emptying Confidential Token Account ========");
+    println!("Account: {}", token_account);
+    println!("\nStep 1: Checking that the account holds no tokens...");
+
+    // Every credit since the last apply (even of zero tokens) must be applied first
+    if u64::from(extension.pending_balance_credit_counter) != 0 {
+        return Err(ConfidentialError::PendingBalanceNotApplied);
+    }
+
+    let balances = decrypt_confidential_balances(extension, elgamal_kp, aes_kp)?;
+    if balances.available != 0 {
+        return Err(ConfidentialError::Invalid(format!(
+            "Account still holds {} confidential tokens, withdraw or transfer them first",
+            format_token_amount(balances.available, decimals)
+        )));
+    }
+    if account.base.amount != 0 {
+        return Err(ConfidentialError::Invalid(format!(
+            "Account still holds {} public tokens, transfer them first",
+            format_token_amount(account.base.amount, decimals)
+        )));
+    }
+    if let Ok(fee_amount) = account.get_extension::<ConfidentialTransferFeeAmount>()
+        && fee_amount.closable().is_err()
+    {
+        return Err(ConfidentialError::Invalid(String::from(
+            "Account holds withheld transfer fees, harvest them to the mint first",
+        )));
+    }
+    println!("✓ Pending, available and public balances are zero");
+
+    // The zero-balance proof is small enough to be verified in the same transaction
+    println!("\nStep 2: Proving the available balance is zero and emptying the account...");
+    let empty_sig = token
+        .confidential_transfer_empty_account(
+            token_account,
+            &owner.pubkey(),
+            None,
+            None,
+            elgamal_kp,
+            &[owner],
+        )
+        .await?;
+    handle_token_response(&empty_sig, String::from("emptying confidential account")).await?;
+
+    println!("\nStep 3: Closing the token account...");
+    let close_sig = token
+        .close_account(
+            token_account,   // Account to close
+            &owner.pubkey(), // Lamports destination
+            &owner.pubkey(), // Account owner
+            &[owner],
+        )
+        .await?;
+    handle_token_response(&close_sig, String::from("closing token account")).await?;
+    println!("✓ Rent returned to {}", owner.pubkey());
+
+    Ok(())
+}
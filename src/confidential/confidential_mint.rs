@@ -1,47 +1,117 @@
-use anyhow::{Ok, Result};
 use solana_sdk::{pubkey::Pubkey, signature::Keypair};
 use spl_token_client::{
-    client::ProgramRpcClientSendTransaction,
+    spl_token_2022::solana_zk_sdk::encryption::{
+        auth_encryption::AeKey, elgamal::ElGamalKeypair, pod::elgamal::PodElGamalPubkey,
+    },
     token::{ExtensionInitializationParams, Token},
 };
 
-use crate::helper::handle_token_response;
+use crate::{
+    error::Result,
+    helper::{handle_token_response, ConfidentialSender},
+};
+
+/// Transfer fee of a confidential mint. Fees of confidential transfers are withheld
+/// encrypted under `withdraw_withheld_authority_elgamal_pubkey`.
+pub struct ConfidentialTransferFee {
+    pub transfer_fee_basis_points: u16, // Fee rate in basis points (1 bp = 0.01%)
+    pub maximum_fee: u64,               // Fee cap per transfer (in base units)
+    pub withdraw_withheld_authority_elgamal_pubkey: PodElGamalPubkey, // Encrypts withheld fees
+}
 
-/// Creates a new confidential mint with the ConfidentialTransfer extension enabled.
+/// Creates a new confidential mint with the ConfidentialTransfer extension enabled and,
+/// optionally, the ConfidentialMintBurn and transfer fee extensions.
 ///
 /// # Arguments
 /// * `mint_authority` - The public key that will have minting authority.
 /// * `signers` - The keypairs required to sign the mint creation transaction.
 /// * `token` - The SPL Token client.
+/// * `auditor_elgamal_pubkey` - Optional auditor ElGamal pubkey. Every confidential transfer
+///   of this mint must also encrypt the transfer amount under this key.
+/// * `auto_approve_new_accounts` - If false, every new confidential token account must be
+///   approved by the confidential transfer authority (`mint_authority`) before it can be used.
+/// * `supply_keys` - If set, adds the ConfidentialMintBurn extension with the given supply
+///   ElGamal keypair and AE key, so tokens are only minted and burned as encrypted amounts.
+/// * `transfer_fee` - If set, adds the TransferFeeConfig and ConfidentialTransferFeeConfig
+///   extensions. `mint_authority` becomes the fee config and withdraw withheld authority.
 ///
 /// # Flow
-/// 1. Sets up the ConfidentialTransfer extension parameters (authority, auto-approve, no auditor).
-/// 2. Calls the SPL Token client to create the mint with the extension.
-/// 3. Prints the transaction signature or logs.
-pub async fn create_confidential_mint(
+/// 1. Sets up the ConfidentialTransfer extension parameters (authority, auto-approve, auditor).
+/// 2. Sets up the ConfidentialMintBurn extension parameters (encrypted zero supply), if enabled.
+/// 3. Sets up the TransferFeeConfig and ConfidentialTransferFeeConfig parameters, if enabled.
+/// 4. Calls the SPL Token client to create the mint with the extension.
+/// 5. Prints the transaction signature or logs.
+pub async fn create_confidential_mint<T: ConfidentialSender>(
     mint_authority: &Pubkey,
     signers: &[&Keypair],
-    token: &Token<ProgramRpcClientSendTransaction>,
+    token: &Token<T>,
+    auditor_elgamal_pubkey: Option<PodElGamalPubkey>,
+    auto_approve_new_accounts: bool,
+    supply_keys: Option<(&ElGamalKeypair, &AeKey)>,
+    transfer_fee: Option<ConfidentialTransferFee>,
 ) -> Result<()> {
     println!("\n======== Creating Confidential Mint Account ========");
     println!("Setting up ConfidentialTransfer extension parameters...");
     println!("- Mint Authority: {}", mint_authority);
-    println!("- Auto-approve new accounts: enabled");
-    println!("- Auditor: disabled (no global auditor)");
+    if auto_approve_new_accounts {
+        println!("- Auto-approve new accounts: enabled");
+    } else {
+        println!("- Auto-approve new accounts: disabled (accounts need manual approval)");
+    }
+    match &auditor_elgamal_pubkey {
+        Some(auditor) => println!("- Auditor ElGamal Pubkey: {}", auditor),
+        None => println!("- Auditor: disabled (no global auditor)"),
+    }
+
+    let mut extension_initialization_params =
+        vec![ExtensionInitializationParams::ConfidentialTransferMint {
+            authority: Some(*mint_authority), // Set the mint authority
+            auto_approve_new_accounts,        // Automatically approve new confidential accounts
+            auditor_elgamal_pubkey,           // Global auditor for this confidential mint (if any)
+        }];
+
+    if let Some((supply_elgamal_kp, supply_aes_kp)) = supply_keys {
+        println!("- Confidential mint/burn: enabled");
+        println!("- Supply ElGamal Pubkey: {}", supply_elgamal_kp.pubkey());
+
+        extension_initialization_params.push(ExtensionInitializationParams::ConfidentialMintBurn {
+            supply_elgamal_pubkey: (*supply_elgamal_kp.pubkey()).into(), // Encrypts the supply
+            decryptable_supply: supply_aes_kp.encrypt(0).into(),         // Supply starts at zero
+        });
+    }
+
+    if let Some(transfer_fee) = transfer_fee {
+        println!(
+            "- Transfer fee: {} basis points (maximum {} base units)",
+            transfer_fee.transfer_fee_basis_points, transfer_fee.maximum_fee
+        );
+        println!(
+            "- Withdraw Withheld Authority ElGamal Pubkey: {}",
+            transfer_fee.withdraw_withheld_authority_elgamal_pubkey
+        );
 
-    let extension_initialization_params = ExtensionInitializationParams::ConfidentialTransferMint {
-        authority: Some(*mint_authority),           // Set the mint authority
-        auto_approve_new_accounts: true,            // Automatically approve new confidential accounts
-        auditor_elgamal_pubkey: None,               // No global auditor for this confidential mint
-    };
+        extension_initialization_params.push(ExtensionInitializationParams::TransferFeeConfig {
+            transfer_fee_config_authority: Some(*mint_authority), // Can update the fee
+            withdraw_withheld_authority: Some(*mint_authority),   // Can withdraw withheld fees
+            transfer_fee_basis_points: transfer_fee.transfer_fee_basis_points,
+            maximum_fee: transfer_fee.maximum_fee,
+        });
+        extension_initialization_params.push(
+            ExtensionInitializationParams::ConfidentialTransferFeeConfig {
+                authority: Some(*mint_authority), // Can enable/disable harvesting to the mint
+                withdraw_withheld_authority_elgamal_pubkey: transfer_fee
+                    .withdraw_withheld_authority_elgamal_pubkey, // Encrypts withheld fees
+            },
+        );
+    }
 
-    // Create the mint account with the ConfidentialTransfer extension
+    // Create the mint account with the confidential extensions
     let create_mint_sig = token
         .create_mint(
-            mint_authority,                        // Mint authority - can mint new tokens
-            Some(mint_authority),                  // Freeze authority - can freeze token accounts
-            vec![extension_initialization_params], // Add the ConfidentialTransferMint extension
-            &[signers[0], signers[1]],             // Mint keypair(s) needed as signer(s)
+            mint_authority,                  // Mint authority - can mint new tokens
+            Some(mint_authority),            // Freeze authority - can freeze token accounts
+            extension_initialization_params, // Add the confidential extensions
+            &[signers[0], signers[1]],       // Mint keypair(s) needed as signer(s)
         )
         .await?;
 
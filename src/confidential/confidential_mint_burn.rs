@@ -0,0 +1,249 @@
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+use spl_token_client::{
+    spl_token_2022::{
+        extension::{
+            confidential_mint_burn::{
+                account_info::{BurnAccountInfo, SupplyAccountInfo},
+                ConfidentialMintBurn,
+            },
+            confidential_transfer::ConfidentialTransferAccount,
+            BaseStateWithExtensions,
+        },
+        solana_zk_sdk::encryption::{
+            auth_encryption::AeKey,
+            elgamal::{ElGamalKeypair, ElGamalPubkey},
+        },
+    },
+    token::{ProofAccountWithCiphertext, Token},
+};
+use spl_token_confidential_transfer_proof_generation::{burn::BurnProofData, mint::MintProofData};
+
+use super::ProofContextAccounts;
+use crate::{
+    error::{ConfidentialError, Result},
+    helper::{
+        fetch_auditor_elgamal_pubkey, fetch_recipient_elgamal_pubkey, format_token_amount,
+        handle_token_response, ConfidentialSender,
+    },
+};
+
+/// Mints tokens directly into the pending confidential balance of a token account.
+/// Requires a mint created with the ConfidentialMintBurn extension.
+///
+/// # Arguments
+/// * `token` - The SPL Token client.
+/// * `authority` - The mint authority.
+/// * `supply_elgamal_kp` - ElGamal keypair encrypting the confidential supply of the mint.
+/// * `supply_aes_kp` - AE key encrypting the decryptable supply of the mint.
+/// * `destination_token_account` - Confidential token account receiving the tokens.
+/// * `amount` - Amount to mint in base units.
+///
+/// # Flow
+/// 1. Reads the destination's ElGamal pubkey and the mint's auditor.
+/// 2. Generates three ZK proofs: equality (new supply), ciphertext validity (mint amount)
+///    and range (mint amount and new supply).
+/// 3. Creates context state accounts for each proof.
+/// 4. Executes the confidential mint referencing the proof accounts.
+/// 5. Closes all proof context state accounts to reclaim rent.
+pub async fn confidential_mint_to<T: ConfidentialSender>(
+    token: &Token<T>,
+    authority: &Keypair,
+    supply_elgamal_kp: &ElGamalKeypair,
+    supply_aes_kp: &AeKey,
+    destination_token_account: &Pubkey,
+    amount: u64,
+) -> Result<()> {
+    // The current confidential supply is needed to prove the new supply
+    let mint = token.get_mint_info().await?;
+    let decimals = mint.base.decimals;
+
+    // The minted amount is encrypted under the destination's ElGamal pubkey
+    let destination_elgamal_pubkey =
+        fetch_recipient_elgamal_pubkey(token, destination_token_account).await?;
+    let auditor_elgamal_pubkey = fetch_auditor_elgamal_pubkey(token).await?;
+
+    let supply_account_info = SupplyAccountInfo::new(mint.get_extension::<ConfidentialMintBurn>()?);
+
+    let MintProofData {
+        equality_proof_data,
+        ciphertext_validity_proof_data_with_ciphertext,
+        range_proof_data,
+    } = supply_account_info.generate_split_mint_proof_data(
+        amount,
+        supply_elgamal_kp,
+        supply_aes_kp,
+        &destination_elgamal_pubkey,
+        auditor_elgamal_pubkey.as_ref(),
+    )?;
+
+    println!("\n======== Confidential Mint ========");
+    println!("- Amount: {} tokens", format_token_amount(amount, decimals));
+    println!("- To: {}", destination_token_account);
+
+    // The proof accounts are closed whether the mint succeeds or not
+    let proof_accounts = ProofContextAccounts::new(token, authority);
+    let result = async {
+        println!(
+            "1. Creating Equality Proof (proves the new supply is the old supply plus the amount)..."
+        );
+        let equality_proof_context_state = proof_accounts.create(&equality_proof_data, false).await?;
+        println!("   ✓ Equality proof created");
+
+        println!(
+            "2. Creating Ciphertext Validity Proof (proves the encrypted amounts are valid)..."
+        );
+        let ciphertext_validity_proof_context_state = proof_accounts
+            .create(
+                &ciphertext_validity_proof_data_with_ciphertext.proof_data,
+                false,
+            )
+            .await?;
+        println!("   ✓ Ciphertext validity proof created");
+
+        println!(
+            "3. Creating Range Proof (proves the amount and new supply are within valid range)..."
+        );
+        let range_proof_context_state = proof_accounts.create(&range_proof_data, true).await?;
+        println!("   ✓ Range proof created");
+
+        let ciphertext_validity_proof_account_with_ciphertext = ProofAccountWithCiphertext {
+            context_state_account: ciphertext_validity_proof_context_state,
+            ciphertext_lo: ciphertext_validity_proof_data_with_ciphertext.ciphertext_lo,
+            ciphertext_hi: ciphertext_validity_proof_data_with_ciphertext.ciphertext_hi,
+        };
+
+        let mint_sig = token
+            .confidential_transfer_mint(
+                &authority.pubkey(),
+                destination_token_account,
+                Some(&equality_proof_context_state),
+                Some(&ciphertext_validity_proof_account_with_ciphertext),
+                Some(&range_proof_context_state),
+                amount,
+                supply_elgamal_kp,
+                &destination_elgamal_pubkey,
+                auditor_elgamal_pubkey.as_ref(),
+                supply_aes_kp,
+                Some(supply_account_info),
+                &[authority],
+            )
+            .await?;
+        handle_token_response(&mint_sig, String::from("confidential mint")).await
+    }
+    .await;
+
+    proof_accounts.close_after(result).await?;
+    println!("Note: The minted tokens land in the pending balance and must be applied");
+
+    Ok(())
+}
+
+/// Burns tokens from the available confidential balance of a token account.
+/// Requires a mint created with the ConfidentialMintBurn extension.
+///
+/// # Arguments
+/// * `token` - The SPL Token client.
+/// * `owner` - The owner of the token account.
+/// * `elgamal_kp` - ElGamal keypair of the token account.
+/// * `aes_kp` - AE key of the token account.
+/// * `token_account` - Confidential token account to burn from.
+/// * `amount` - Amount to burn in base units.
+///
+/// # Flow
+/// 1. Reads the supply ElGamal pubkey and the auditor from the mint.
+/// 2. Generates three ZK proofs: equality (remaining balance), ciphertext validity (burn
+///    amount) and range (burn amount and remaining balance).
+/// 3. Creates context state accounts for each proof.
+/// 4. Executes the confidential burn referencing the proof accounts.
+/// 5. Closes all proof context state accounts to reclaim rent.
+pub async fn confidential_burn<T: ConfidentialSender>(
+    token: &Token<T>,
+    owner: &Keypair,
+    elgamal_kp: &ElGamalKeypair,
+    aes_kp: &AeKey,
+    token_account: &Pubkey,
+    amount: u64,
+) -> Result<()> {
+    let mint = token.get_mint_info().await?;
+    let decimals = mint.base.decimals;
+
+    // The burned amount is encrypted under the supply ElGamal pubkey of the mint
+    let supply_elgamal_pubkey = ElGamalPubkey::try_from(
+        mint.get_extension::<ConfidentialMintBurn>()?
+            .supply_elgamal_pubkey,
+    )
+    .map_err(|e| {
+        ConfidentialError::Invalid(format!("Invalid supply ElGamal pubkey on mint: {e}"))
+    })?;
+    let auditor_elgamal_pubkey = fetch_auditor_elgamal_pubkey(token).await?;
+
+    let account = token.get_account_info(token_account).await?;
+    let burn_account_info =
+        BurnAccountInfo::new(account.get_extension::<ConfidentialTransferAccount>()?);
+
+    let BurnProofData {
+        equality_proof_data,
+        ciphertext_validity_proof_data_with_ciphertext,
+        range_proof_data,
+    } = burn_account_info.generate_split_burn_proof_data(
+        amount,
+        elgamal_kp,
+        aes_kp,
+        &supply_elgamal_pubkey,
+        auditor_elgamal_pubkey.as_ref(),
+    )?;
+
+    println!("\n======== Confidential Burn ========");
+    println!("- Amount: {} tokens", format_token_amount(amount, decimals));
+    println!("- From: {}", token_account);
+
+    // The proof accounts are closed whether the burn succeeds or not
+    let proof_accounts = ProofContextAccounts::new(token, owner);
+    let result = async {
+        println!("1. Creating Equality Proof (proves the remaining balance is correct)...");
+        let equality_proof_context_state = proof_accounts.create(&equality_proof_data, false).await?;
+        println!("   ✓ Equality proof created");
+
+        println!(
+            "2. Creating Ciphertext Validity Proof (proves the encrypted amounts are valid)..."
+        );
+        let ciphertext_validity_proof_context_state = proof_accounts
+            .create(
+                &ciphertext_validity_proof_data_with_ciphertext.proof_data,
+                false,
+            )
+            .await?;
+        println!("   ✓ Ciphertext validity proof created");
+
+        println!("3. Creating Range Proof (proves the amount and remaining balance are within valid range)...");
+        let range_proof_context_state = proof_accounts.create(&range_proof_data, true).await?;
+        println!("   ✓ Range proof created");
+
+        let ciphertext_validity_proof_account_with_ciphertext = ProofAccountWithCiphertext {
+            context_state_account: ciphertext_validity_proof_context_state,
+            ciphertext_lo: ciphertext_validity_proof_data_with_ciphertext.ciphertext_lo,
+            ciphertext_hi: ciphertext_validity_proof_data_with_ciphertext.ciphertext_hi,
+        };
+
+        let burn_sig = token
+            .confidential_transfer_burn(
+                &owner.pubkey(),
+                token_account,
+                Some(&equality_proof_context_state),
+                Some(&ciphertext_validity_proof_account_with_ciphertext),
+                Some(&range_proof_context_state),
+                amount,
+                elgamal_kp,
+                &supply_elgamal_pubkey,
+                auditor_elgamal_pubkey.as_ref(),
+                aes_kp,
+                Some(burn_account_info),
+                &[owner],
+            )
+            .await?;
+        handle_token_response(&burn_sig, String::from("confidential burn")).await
+    }
+    .await;
+
+    proof_accounts.close_after(result).await
+}
@@ -0,0 +1,327 @@
+use bytemuck::Pod;
+use solana_account_decoder_client_types::{UiAccountEncoding, UiDataSliceConfig};
+use solana_client::{
+    nonblocking::rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+    transaction::Transaction,
+};
+use spl_token_client::{
+    spl_token_2022::solana_zk_sdk::zk_elgamal_proof_program::{
+        self,
+        instruction::{close_context_state, ContextStateInfo},
+        proof_data::{BatchedRangeProofU256Data, ZkProofData},
+    },
+    token::{Token, TokenError as TokenClientError},
+};
+use std::{
+    mem,
+    sync::{Mutex, PoisonError},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    error::Result,
+    helper::{
+        create_range_proof_u256_context_state_account, handle_token_response, ConfidentialSender,
+    },
+};
+
+// =================== Constants ===================
+
+/// Proof context state accounts untouched for this long are no longer used by a running
+/// operation and can be reclaimed.
+pub const DEFAULT_STALE_PROOF_ACCOUNT_AGE: Duration = Duration::from_secs(300);
+
+const CLOSES_PER_TRANSACTION: usize = 8;
+
+// =================== Proof Account Guard ===================
+
+/// Proof context state accounts created for a single operation (transfer, withdraw, mint or
+/// burn), all with the same authority.
+///
+/// Every account is recorded before it is created, so [`ProofContextAccounts::close_after`]
+/// closes whatever was created, whether the operation succeeded or failed halfway. Accounts
+/// can be created concurrently, e.g. all proofs of a transfer at once. Accounts
+/// still open when the guard is dropped (e.g. the operation was cancelled) are reported, and
+/// can be closed later with [`reclaim_proof_accounts`].
+pub struct ProofContextAccounts<'a, T: ConfidentialSender> {
+    token: &'a Token<T>,
+    authority: &'a Keypair, // Context state authority, receives the rent when closing
+    context_state_keypairs: Mutex<Vec<Keypair>>,
+}
+
+impl<'a, T: ConfidentialSender> ProofContextAccounts<'a, T> {
+    pub fn new(token: &'a Token<T>, authority: &'a Keypair) -> Self {
+        ProofContextAccounts {
+            token,
+            authority,
+            context_state_keypairs: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Creates a context state account holding the verified `proof_data` and returns its
+    /// address. `split` verifies the proof in a second transaction, for proofs too large to
+    /// share a transaction with the account creation (e.g. range proofs).
+    pub async fn create<ZK, U>(&self, proof_data: &ZK, split: bool) -> Result<Pubkey>
+    where
+        ZK: Pod + ZkProofData<U>,
+        U: Pod,
+    {
+        let context_state_keypair = self.record();
+        let signature = self
+            .token
+            .confidential_transfer_create_context_state_account(
+                &context_state_keypair.pubkey(),
+                &self.authority.pubkey(),
+                proof_data,
+                split,
+                &[&context_state_keypair],
+            )
+            .await?;
+        handle_token_response(
+            &signature,
+            format!("{:?} proof context state account", ZK::PROOF_TYPE),
+        )
+        .await?;
+
+        Ok(context_state_keypair.pubkey())
+    }
+
+    /// Creates a context state account holding a verified 256-bit batched range proof, see
+    /// [`create_range_proof_u256_context_state_account`], and returns its address.
+    pub async fn create_range_proof_u256(
+        &self,
+        proof_data: &BatchedRangeProofU256Data,
+    ) -> Result<Pubkey> {
+        let context_state_keypair = self.record();
+        create_range_proof_u256_context_state_account(
+            self.token,
+            &context_state_keypair,
+            self.authority,
+            proof_data,
+        )
+        .await?;
+
+        Ok(context_state_keypair.pubkey())
+    }
+
+    /// Closes the accounts after the operation, then returns its `result`. When the operation
+    /// failed, its error is returned even if closing fails as well.
+    pub async fn close_after<R>(self, result: Result<R>) -> Result<R> {
+        match result {
+            Ok(value) => {
+                self.close().await?;
+                Ok(value)
+            }
+            Err(error) => {
+                // Failures to close are already reported, the operation's error matters more
+                let _ = self.close().await;
+                Err(error)
+            }
+        }
+    }
+
+    /// Closes every recorded account that exists and returns its rent to the authority.
+    /// Keeps going when an account fails to close and returns the first error.
+    pub async fn close(mut self) -> Result<()> {
+        let context_state_keypairs = mem::take(self.recorded());
+        if context_state_keypairs.is_empty() {
+            return Ok(());
+        }
+
+        println!("Closing all proof context state account...");
+        let mut result = Ok(());
+        for context_state_keypair in &context_state_keypairs {
+            let context_state_account = context_state_keypair.pubkey();
+
+            // Accounts whose creation failed (or was only simulated) do not exist
+            match self.token.get_account(context_state_account).await {
+                Err(TokenClientError::AccountNotFound) => continue,
+                Err(error) => {
+                    result = result.and(Err(error.into()));
+                    continue;
+                }
+                Ok(_) => {}
+            }
+
+            if let Err(error) = self
+                .token
+                .confidential_transfer_close_context_state_account(
+                    &context_state_account,
+                    &self.authority.pubkey(),
+                    &self.authority.pubkey(),
+                    &[self.authority],
+                )
+                .await
+            {
+                println!(
+                    "⚠️ Failed to close proof context state account {}: {}",
+                    context_state_account, error
+                );
+                result = result.and(Err(error.into()));
+            }
+        }
+
+        match &result {
+            Ok(()) => println!("Closed all context state accounts"),
+            Err(_) => println!(
+                "⚠️ Run `reclaim-proof-accounts` later to recover the rent of the accounts left open"
+            ),
+        }
+        result
+    }
+
+    /// Adds a new context state keypair, before the account is created.
+    fn record(&self) -> Keypair {
+        let context_state_keypair = Keypair::new();
+        self.context_state_keypairs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(context_state_keypair.insecure_clone());
+        context_state_keypair
+    }
+
+    fn recorded(&mut self) -> &mut Vec<Keypair> {
+        self.context_state_keypairs
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T: ConfidentialSender> Drop for ProofContextAccounts<'_, T> {
+    fn drop(&mut self) {
+        // Only reached with recorded accounts when the operation never got to `close`
+        let open_accounts = self.recorded().len();
+        if open_accounts > 0 {
+            println!(
+                "⚠️ {} proof context state account(s) may be left open, run `reclaim-proof-accounts` to recover their rent",
+                open_accounts
+            );
+        }
+    }
+}
+
+// =================== Reclaim Proof Accounts ===================
+
+/// An open proof context state account whose rent can be reclaimed.
+#[derive(Debug, Clone)]
+pub struct StaleProofAccount {
+    pub address: Pubkey,
+    pub lamports: u64,          // Rent returned to the authority when closing
+    pub last_used: Option<i64>, // Unix timestamp of the last transaction touching the account
+}
+
+/// Finds the proof context state accounts of `authority` whose last transaction is at least
+/// `min_age` old. Accounts still used by a running operation are younger and skipped.
+/// Accounts without a known transaction time could still be in use and are only included
+/// with `include_unknown`.
+///
+/// Needs an RPC node with `getProgramAccounts`; the in-process bank of the tests has none.
+pub async fn find_stale_proof_accounts(
+    rpc_client: &RpcClient,
+    authority: &Pubkey,
+    min_age: Duration,
+    include_unknown: bool,
+) -> Result<Vec<StaleProofAccount>> {
+    // Every context state account starts with its authority (`ProofContextStateMeta`)
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+            0,
+            authority.to_bytes().to_vec(),
+        ))]),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            data_slice: Some(UiDataSliceConfig {
+                offset: 0,
+                length: 0,
+            }), // Only the address and the lamports are needed
+            ..RpcAccountInfoConfig::default()
+        },
+        ..RpcProgramAccountsConfig::default()
+    };
+    let accounts = rpc_client
+        .get_program_accounts_with_config(&zk_elgamal_proof_program::id(), config)
+        .await?;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let mut stale_accounts = Vec::new();
+    for (address, account) in accounts {
+        // Signatures are returned newest first
+        let last_used = rpc_client
+            .get_signatures_for_address(&address)
+            .await?
+            .first()
+            .and_then(|status| status.block_time);
+        let stale = match last_used {
+            Some(time) => now - time >= min_age.as_secs() as i64,
+            None => include_unknown,
+        };
+        if !stale {
+            continue;
+        }
+        stale_accounts.push(StaleProofAccount {
+            address,
+            lamports: account.lamports,
+            last_used,
+        });
+    }
+
+    Ok(stale_accounts)
+}
+
+/// Closes proof context state accounts of `authority`, returning their rent to it. The
+/// authority signs and pays for the transactions.
+///
+/// # Returns
+/// * `Vec<Signature>` - One signature per transaction, each closing up to 8 accounts.
+pub async fn reclaim_proof_accounts(
+    rpc_client: &RpcClient,
+    authority: &Keypair,
+    accounts: &[Pubkey],
+) -> Result<Vec<Signature>> {
+    let authority_pubkey = authority.pubkey();
+
+    let mut signatures = Vec::new();
+    for chunk in accounts.chunks(CLOSES_PER_TRANSACTION) {
+        let close_ixs: Vec<_> = chunk
+            .iter()
+            .map(|context_state_account| {
+                close_context_state(
+                    ContextStateInfo {
+                        context_state_account,
+                        context_state_authority: &authority_pubkey,
+                    },
+                    &authority_pubkey,
+                )
+            })
+            .collect();
+
+        let blockhash = rpc_client.get_latest_blockhash().await?;
+        let transaction = Transaction::new_signed_with_payer(
+            &close_ixs,
+            Some(&authority_pubkey),
+            &[authority],
+            blockhash,
+        );
+        let signature = rpc_client
+            .send_and_confirm_transaction(&transaction)
+            .await?;
+        println!(
+            "✓ Closed {} proof context state account(s): {}",
+            chunk.len(),
+            signature
+        );
+        signatures.push(signature);
+    }
+
+    Ok(signatures)
+}
@@ -0,0 +1,89 @@
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+use spl_token_client::{
+    spl_token_2022::{
+        extension::{confidential_transfer::ConfidentialTransferAccount, BaseStateWithExtensions},
+        solana_zk_sdk::encryption::pod::elgamal::PodElGamalPubkey,
+    },
+    token::Token,
+};
+
+use crate::{
+    error::{ConfidentialError, Result},
+    helper::{
+        decrypt_confidential_balances, derive_confidential_keys, format_token_amount,
+        ConfTokenAccountRes, ConfidentialSender,
+    },
+};
+
+/// Recreates the ElGamal keypair and AE key of a confidential token account from the owner
+/// keypair alone and checks them against the account on-chain.
+///
+/// # Arguments
+/// * `token` - The SPL Token client.
+/// * `owner` - The owner of the token account, whose signature the keys are derived from.
+/// * `token_account` - Confidential token account to recover the keys of.
+///
+/// # Flow
+/// 1. Checks that `owner` owns the token account.
+/// 2. Derives both keys from the owner's signature over the token account address.
+/// 3. Checks that the derived ElGamal pubkey matches the one stored in the account.
+/// 4. Decrypts the pending and available balances, which also checks the AE key.
+///
+/// # Returns
+/// * `ConfTokenAccountRes` - The token account and its recovered keys (without a keypair).
+pub async fn recover_confidential_keys<T: ConfidentialSender>(
+    token: &Token<T>,
+    owner: &Keypair,
+    token_account: &Pubkey,
+) -> Result<ConfTokenAccountRes> {
+    let decimals = token.get_mint_info().await?.base.decimals;
+    let account = token.get_account_info(token_account).await?;
+
+    println!("\n======== Recovering Confidential Keys ========");
+    println!("Account: {}", token_account);
+    println!("Owner: {}", owner.pubkey());
+
+    println!("\nStep 1: Checking the account owner...");
+    if account.base.owner != owner.pubkey() {
+        return Err(ConfidentialError::Invalid(format!(
+            "Token account {} is owned by {}, not {}",
+            token_account,
+            account.base.owner,
+            owner.pubkey()
+        )));
+    }
+    println!("✓ Owner matches");
+
+    println!("\nStep 2: Deriving ElGamal keypair and AE key from the owner's signature...");
+    let (elgamal_kp, aes_kp) = derive_confidential_keys(owner, token_account)?;
+
+    println!("\nStep 3: Comparing the derived ElGamal pubkey with the on-chain one...");
+    let extension = account.get_extension::<ConfidentialTransferAccount>()?;
+    let derived_elgamal_pubkey = PodElGamalPubkey::from(*elgamal_kp.pubkey());
+    println!("- Derived:  {}", derived_elgamal_pubkey);
+    println!("- On-chain: {}", extension.elgamal_pubkey);
+    if extension.elgamal_pubkey != derived_elgamal_pubkey {
+        return Err(ConfidentialError::ElGamalPubkeyMismatch);
+    }
+    println!("✓ ElGamal pubkey matches");
+
+    // A wrong AE key cannot decrypt the decryptable available balance
+    println!("\nStep 4: Decrypting the confidential balances...");
+    let balances = decrypt_confidential_balances(extension, &elgamal_kp, &aes_kp)?;
+    println!(
+        "- Pending balance: {} tokens",
+        format_token_amount(balances.pending, decimals)
+    );
+    println!(
+        "- Available balance: {} tokens",
+        format_token_amount(balances.available, decimals)
+    );
+    println!("✓ Keys recovered");
+
+    Ok(ConfTokenAccountRes {
+        token_account: *token_account,
+        token_account_kp: None,
+        user_elgamal_kp: elgamal_kp,
+        user_aes_kp: aes_kp,
+    })
+}
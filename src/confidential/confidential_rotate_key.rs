@@ -0,0 +1,233 @@
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+use spl_token_client::{
+    client::ProgramClient,
+    spl_token_2022::{
+        extension::{
+            confidential_transfer::{ConfidentialTransferAccount, ConfidentialTransferMint},
+            confidential_transfer_fee::ConfidentialTransferFeeAmount,
+            transfer_fee::{TransferFeeAmount, TransferFeeConfig},
+            BaseStateWithExtensions,
+        },
+        solana_zk_sdk::encryption::{auth_encryption::AeKey, elgamal::ElGamalKeypair},
+    },
+    token::Token,
+};
+
+use super::{
+    apply_pending, create_confidential_token_acc, empty_account, harvest_withheld_fees,
+    set_confidential_credits, set_non_confidential_credits, transfer_tokens,
+};
+use crate::{
+    error::{ConfidentialError, Result},
+    helper::{
+        associated_token_address, decrypt_confidential_balances, fetch_current_epoch,
+        format_token_amount, handle_token_response, ConfTokenAccountRes, ConfidentialSender,
+    },
+};
+
+/// Rotates the ElGamal key of a confidential token account, e.g. after its secret leaked.
+///
+/// Token-2022 configures the ConfidentialTransfer extension of an account only once, so the
+/// new ElGamal pubkey is configured on a new token account of the same owner and the
+/// balances are moved there. The old account is emptied with a zero-balance proof and closed.
+///
+/// # Arguments
+/// * `token` - The SPL Token client.
+/// * `program_client` - The program client of `token` (RPC node or in-process bank).
+/// * `owner` - The owner of the token account (also pays for the new account).
+/// * `elgamal_kp` - Current ElGamal keypair of the token account.
+/// * `aes_kp` - Current AE key of the token account.
+/// * `token_account` - Confidential token account whose key is rotated. The owner's associated
+///   token account is refused, as it would be closed without being recreated.
+/// * `allow_transfer_fee` - Accept paying the mint's transfer fee on the moved balances.
+///   Without it, rotating an account with a non-zero fee on a fee mint is refused.
+///
+/// # Flow
+/// 1. Creates a new token account configured with a new ElGamal pubkey and validity proof.
+/// 2. Refuses further credits to the old account and applies its pending balance.
+/// 3. Transfers the available balance confidentially and the public balance publicly.
+/// 4. Harvests the confidential and public transfer fees withheld in the old account to the
+///    mint.
+/// 5. Empties the old account with a zero-balance proof and closes it.
+/// 6. Applies the pending balance of the new account.
+///
+/// # Returns
+/// * `ConfTokenAccountRes` - The new token account, its keypair and its cryptographic keys.
+pub async fn rotate_elgamal_key<T: ConfidentialSender>(
+    token: &Token<T>,
+    program_client: &dyn ProgramClient<T>,
+    owner: &Keypair,
+    elgamal_kp: &ElGamalKeypair,
+    aes_kp: &AeKey,
+    token_account: &Pubkey,
+    allow_transfer_fee: bool,
+) -> Result<ConfTokenAccountRes> {
+    let mint = token.get_mint_info().await?;
+    let decimals = mint.base.decimals;
+    let account = token.get_account_info(token_account).await?;
+    let extension = account.get_extension::<ConfidentialTransferAccount>()?;
+
+    println!("\n======== Rotating ElGamal Key ========");
+    println!("Account: {}", token_account);
+
+    // The new account gets a new address, so the associated token address would be left closed
+    if *token_account == associated_token_address(&owner.pubkey(), token.get_address()) {
+        return Err(ConfidentialError::Invalid(String::from(
+            "The account is the owner's associated token account, which rotation would close without recreating it. Move the balances to a new account manually",
+        )));
+    }
+    // A new account waiting for approval could not receive the balances
+    if !bool::from(
+        mint.get_extension::<ConfidentialTransferMint>()?
+            .auto_approve_new_accounts,
+    ) {
+        return Err(ConfidentialError::Invalid(String::from(
+            "The mint requires approval of new accounts, rotate after creating and approving a new account manually",
+        )));
+    }
+    // Fails early if the current keys do not belong to the account
+    let balances = decrypt_confidential_balances(extension, elgamal_kp, aes_kp)?;
+
+    // The balances move with ordinary transfers, so on a fee mint the owner pays the fee on
+    // their own tokens: once on the confidential balance and once on the public balance
+    if let Ok(transfer_fee_config) = mint.get_extension::<TransferFeeConfig>() {
+        let epoch = fetch_current_epoch(token).await?;
+        let fee = |amount: u64| {
+            transfer_fee_config
+                .calculate_epoch_fee(epoch, amount)
+                .ok_or_else(|| {
+                    ConfidentialError::Invalid(String::from("Transfer fee calculation overflow"))
+                })
+        };
+        let confidential = balances.pending.saturating_add(balances.available);
+        let total_fee = fee(confidential)?.saturating_add(fee(account.base.amount)?);
+        if total_fee != 0 {
+            if !allow_transfer_fee {
+                return Err(ConfidentialError::Invalid(format!(
+                    "The mint charges a transfer fee, moving the balances would cost {} tokens. Rotate with the transfer fee allowed to accept it",
+                    format_token_amount(total_fee, decimals)
+                )));
+            }
+            println!(
+                "⚠️ Moving the balances pays a transfer fee of {} tokens",
+                format_token_amount(total_fee, decimals)
+            );
+        }
+    }
+    let maximum_pending_balance_credit_counter =
+        u64::from(extension.maximum_pending_balance_credit_counter);
+
+    println!("\n[1/6] Creating a new token account with a new ElGamal key...");
+    let new_account = create_confidential_token_acc(
+        owner,
+        token.get_address(),
+        maximum_pending_balance_credit_counter,
+        false,
+        program_client,
+        token,
+    )
+    .await?;
+    let new_token_account = new_account.token_account;
+    println!("✓ New account {}", new_token_account);
+
+    println!("\n[2/6] Refusing new credits to the old account and applying its pending balance...");
+    set_confidential_credits(token, owner, token_account, false).await?;
+    set_non_confidential_credits(token, owner, token_account, false).await?;
+    let account = token.get_account_info(token_account).await?;
+    let extension = account.get_extension::<ConfidentialTransferAccount>()?;
+    if u64::from(extension.pending_balance_credit_counter) != 0 {
+        apply_pending(token, owner, elgamal_kp, aes_kp, token_account).await?;
+    }
+
+    println!("\n[3/6] Moving the balances to the new account...");
+    let account = token.get_account_info(token_account).await?;
+    let extension = account.get_extension::<ConfidentialTransferAccount>()?;
+    let available = decrypt_confidential_balances(extension, elgamal_kp, aes_kp)?.available;
+    if available != 0 {
+        transfer_tokens(
+            available,
+            token,
+            token_account,
+            elgamal_kp,
+            aes_kp,
+            owner,
+            &new_token_account,
+        )
+        .await?;
+    }
+    println!(
+        "✓ Moved {} confidential tokens",
+        format_token_amount(available, decimals)
+    );
+
+    let public = account.base.amount;
+    if public != 0 {
+        let transfer_sig = token
+            .transfer(
+                token_account,      // Source
+                &new_token_account, // Destination
+                &owner.pubkey(),    // Source owner
+                public,             // Amount in base units
+                &[owner],
+            )
+            .await?;
+        handle_token_response(&transfer_sig, String::from("moving public balance")).await?;
+    }
+    println!(
+        "✓ Moved {} public tokens",
+        format_token_amount(public, decimals)
+    );
+
+    // Closing is refused while the account withholds fees of either kind. Fees withheld by
+    // earlier incoming transfers stay in the old account, the moved balances withhold theirs
+    // in the new one
+    println!("\n[4/6] Harvesting withheld transfer fees of the old account...");
+    let account = token.get_account_info(token_account).await?;
+    let withheld_confidential = account
+        .get_extension::<ConfidentialTransferFeeAmount>()
+        .is_ok_and(|fee_amount| fee_amount.closable().is_err());
+    let withheld_public = account
+        .get_extension::<TransferFeeAmount>()
+        .map_or(0, |fee_amount| u64::from(fee_amount.withheld_amount));
+    if withheld_confidential {
+        harvest_withheld_fees(token, &[*token_account]).await?;
+    }
+    if withheld_public != 0 {
+        let harvest_sig = token
+            .harvest_withheld_tokens_to_mint(&[token_account])
+            .await?;
+        handle_token_response(
+            &harvest_sig,
+            String::from("harvesting withheld public fees"),
+        )
+        .await?;
+        println!(
+            "✓ Harvested {} withheld public tokens",
+            format_token_amount(withheld_public, decimals)
+        );
+    }
+    if !withheld_confidential && withheld_public == 0 {
+        println!("✓ No withheld fees");
+    }
+
+    println!("\n[5/6] Emptying and closing the old account...");
+    empty_account(token, owner, elgamal_kp, aes_kp, token_account).await?;
+
+    println!("\n[6/6] Applying the pending balance of the new account...");
+    if available != 0 {
+        apply_pending(
+            token,
+            owner,
+            &new_account.user_elgamal_kp,
+            &new_account.user_aes_kp,
+            &new_token_account,
+        )
+        .await?;
+    }
+    println!(
+        "✓ ElGamal key rotated, the tokens now live in {}",
+        new_token_account
+    );
+
+    Ok(new_account)
+}
@@ -1,123 +1,207 @@
-use anyhow::{Ok, Result};
-use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_sdk::{signature::Keypair, signer::Signer, system_instruction};
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+use solana_system_interface::instruction as system_instruction;
+use spl_associated_token_account::instruction::create_associated_token_account;
 use spl_token_client::{
-    client::ProgramRpcClientSendTransaction,
+    client::ProgramClient,
     spl_token_2022::{
         self,
         extension::{
             confidential_transfer::instruction::{configure_account, PubkeyValidityProofData},
-            ExtensionType,
+            BaseStateWithExtensions, ExtensionType,
         },
-        instruction::initialize_account3,
-        solana_zk_sdk::encryption::{auth_encryption::AeKey, elgamal::ElGamalKeypair},
+        instruction::{initialize_account3, reallocate},
         state::Account,
     },
     token::Token,
 };
 use spl_token_confidential_transfer_proof_extraction::instruction::ProofLocation;
+use std::num::NonZeroI8;
+
+use super::is_account_approved;
+use crate::{
+    error::{ConfidentialError, Result},
+    helper::{
+        associated_token_address, derive_confidential_keys, handle_token_response,
+        ConfTokenAccountRes, ConfidentialSender,
+    },
+};
 
-use crate::helper::{complete_ixs, ConfTokenAccountRes};
+/// The pubkey validity proof instruction directly follows the `ConfigureAccount` instruction.
+const PROOF_INSTRUCTION_OFFSET: NonZeroI8 = NonZeroI8::new(1).unwrap();
+
+/// Number of incoming credits (transfers, deposits, confidential mints) an account accepts
+/// before its pending balance has to be applied.
+pub const DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER: u64 = 65536;
 
 /// Creates a new confidential token account with the ConfidentialTransfer extension enabled.
 ///
 /// # Arguments
-/// * `payer` - The keypair paying for account creation and rent.
-/// * `mint_kp` - The mint keypair for the token.
-/// * `rpc_client` - The Solana RPC client.
+/// * `payer` - The keypair paying for account creation and rent (also the account owner).
+/// * `mint` - The mint address of the token.
+/// * `maximum_pending_balance_credit_counter` - Credits accepted before the pending balance
+///   must be applied; further incoming transfers are rejected until then.
+/// * `associated` - Create the account at the owner's associated token address instead of
+///   a fresh keypair, so it can be found again from the owner and mint alone.
+/// * `program_client` - The program client of `token` (RPC node or in-process bank).
 /// * `token` - The SPL Token client.
 ///
 /// # Returns
-/// * `ConfTokenAccountRes` - Struct containing the new token account keypair and cryptographic keys.
-pub async fn create_confidential_token_acc(
+/// * `ConfTokenAccountRes` - Struct containing the new token account (and its keypair, if any)
+///   and cryptographic keys.
+pub async fn create_confidential_token_acc<T: ConfidentialSender>(
     payer: &Keypair,
-    mint_kp: &Keypair,
+    mint: &Pubkey,
+    maximum_pending_balance_credit_counter: u64,
+    associated: bool,
 
-    rpc_client: &RpcClient,
-    token: &Token<ProgramRpcClientSendTransaction>,
+    program_client: &dyn ProgramClient<T>,
+    token: &Token<T>,
 ) -> Result<ConfTokenAccountRes> {
     println!("\n======== Creating New Confidential Token Account ========");
-    // Generate a new keypair for the user's token account
-    let token_account_kp = Keypair::new();
-    println!("Generated new token account: {}", token_account_kp.pubkey());
+    // Either the owner's associated token address or a new keypair for the user's token account
+    let token_account_kp = (!associated).then(Keypair::new);
+    let token_account = match &token_account_kp {
+        Some(token_account_kp) => {
+            println!("Generated new token account: {}", token_account_kp.pubkey());
+            token_account_kp.pubkey()
+        }
+        None => {
+            let token_account = associated_token_address(&payer.pubkey(), mint);
+            println!("Associated token account: {}", token_account);
+            token_account
+        }
+    };
 
     println!("Generating cryptographic keys for confidential transactions...");
     // Generate ElGamal and AES keys for confidential encryption, unique to this account
-    let elgamal_kp = ElGamalKeypair::new_from_signer(&payer, &token_account_kp.pubkey().to_bytes())
-        .expect("Unable to create Elgamal KP");
+    let (elgamal_kp, aes_kp) = derive_confidential_keys(payer, &token_account)?;
     println!("Created ElGamal keypair for confidential encryption");
-
-    let aes_kp = AeKey::new_from_signer(&payer, &token_account_kp.pubkey().to_bytes())
-        .expect("Unable to create AES KP");
     println!("Created AES key for confidential encryption");
 
-    println!("\nCalculating account space and rent requirements...");
-    // Calculate the required space for the account with the ConfidentialTransfer extension
-    let required_space = ExtensionType::try_calculate_account_len::<Account>(&[
-        ExtensionType::ConfidentialTransferAccount,
-    ])?;
-    println!("Required account space: {} bytes", required_space);
-
-    // Get the minimum balance needed to make the account rent-exempt
-    let rent_req = rpc_client
-        .get_minimum_balance_for_rent_exemption(required_space)
-        .await?;
-    println!("Required rent (lamports): {}", rent_req);
-
-    // Instruction to create the new token account
-    let create_account_ix = system_instruction::create_account(
-        &payer.pubkey(),
-        &token_account_kp.pubkey(),
-        rent_req,
-        required_space as u64,
-        &spl_token_2022::ID,
-    );
-
-    // Instruction to initialize the token account for the given mint
-    let intialize_token_account_ix = initialize_account3(
-        &spl_token_2022::ID,
-        &token_account_kp.pubkey(),
-        &mint_kp.pubkey(),
-        &payer.pubkey(),
-    )?;
+    // Confidential transfer fees are withheld encrypted in the destination account
+    let mint_extension_types = token.get_mint_info().await?.get_extension_types()?;
+    let mut confidential_extension_types = vec![ExtensionType::ConfidentialTransferAccount];
+    if mint_extension_types.contains(&ExtensionType::TransferFeeConfig) {
+        confidential_extension_types.push(ExtensionType::ConfidentialTransferFeeAmount);
+    }
+
+    let mut ix = match &token_account_kp {
+        Some(token_account_kp) => {
+            println!("\nCalculating account space and rent requirements...");
+            // Calculate the required space for the account with the confidential extensions,
+            // plus the account extensions required by the mint (e.g. TransferFeeAmount for fee mints)
+            let mut account_extension_types =
+                ExtensionType::get_required_init_account_extensions(&mint_extension_types);
+            account_extension_types.extend(&confidential_extension_types);
+            let required_space =
+                ExtensionType::try_calculate_account_len::<Account>(&account_extension_types)?;
+            println!("Required account space: {} bytes", required_space);
+
+            // Get the minimum balance needed to make the account rent-exempt
+            let rent_req = program_client
+                .get_minimum_balance_for_rent_exemption(required_space)
+                .await?;
+            println!("Required rent (lamports): {}", rent_req);
+
+            // Instruction to create the new token account
+            let create_account_ix = system_instruction::create_account(
+                &payer.pubkey(),
+                &token_account_kp.pubkey(),
+                rent_req,
+                required_space as u64,
+                &spl_token_2022::ID,
+            );
+
+            // Instruction to initialize the token account for the given mint
+            let intialize_token_account_ix = initialize_account3(
+                &spl_token_2022::ID,
+                &token_account_kp.pubkey(),
+                mint,
+                &payer.pubkey(),
+            )?;
+
+            vec![create_account_ix, intialize_token_account_ix]
+        }
+        None => {
+            // The associated token account program sizes the account for the mint's required
+            // extensions only, so it is reallocated to make room for the confidential ones
+            let create_associated_account_ix = create_associated_token_account(
+                &payer.pubkey(),
+                &payer.pubkey(),
+                mint,
+                &spl_token_2022::ID,
+            );
+            let reallocate_ix = reallocate(
+                &spl_token_2022::ID,
+                &token_account,
+                &payer.pubkey(),
+                &payer.pubkey(),
+                &[],
+                &confidential_extension_types,
+            )?;
+
+            vec![create_associated_account_ix, reallocate_ix]
+        }
+    };
 
     // Generate a ZK proof to prove the validity of the ElGamal public key
     let proof_data = PubkeyValidityProofData::new(&elgamal_kp)
-        .map_err(|_| anyhow::anyhow!("Failed to generate proof data"))?;
+        .map_err(|e| ConfidentialError::ProofGeneration(e.to_string()))?;
 
     // Specify where the proof data is located in the transaction
-    let proof_location = ProofLocation::InstructionOffset(1.try_into()?, &proof_data);
+    let proof_location = ProofLocation::InstructionOffset(PROOF_INSTRUCTION_OFFSET, &proof_data);
 
     // Instruction to configure the confidential transfer extension for the account
     let confidential_transfer_account_ix = configure_account(
         &spl_token_2022::id(),
-        &token_account_kp.pubkey(),
-        &mint_kp.pubkey(),
+        &token_account,
+        mint,
         &aes_kp.encrypt(0).into(), // Initial encrypted balance is zero
-        65536,                     // Maximum pending balance credit counter
+        maximum_pending_balance_credit_counter,
         &payer.pubkey(),
         &[],
         proof_location,
     )?;
 
     // Combine all instructions into a single transaction
-    let mut ix = vec![create_account_ix, intialize_token_account_ix];
     ix.extend(confidential_transfer_account_ix);
 
     // Submit the transaction to create and configure the confidential token account
-    complete_ixs(rpc_client, ix, &[&payer, &token_account_kp], &payer).await?;
+    let mut signers = vec![payer];
+    signers.extend(&token_account_kp);
+    let create_sig = token.process_ixs(&ix, &signers).await?;
+    handle_token_response(
+        &create_sig,
+        String::from("creating confidential token account"),
+    )
+    .await?;
 
     // Enable confidential transfers for the new token account
     token
         .confidential_transfer_enable_confidential_credits(
-            &token_account_kp.pubkey(),
+            &token_account,
             &payer.pubkey(),
-            &[&payer, &token_account_kp],
+            &[payer],
         )
         .await?;
 
+    // Mints without auto-approval require the confidential transfer authority to approve the account
+    if is_account_approved(&token_account, token).await? {
+        println!("✓ Account is approved for confidential transfers");
+    } else {
+        println!(
+            "⏳ Account {} is pending approval by the mint's confidential transfer authority",
+            token_account
+        );
+    }
+    println!(
+        "Maximum pending balance credit counter: {}",
+        maximum_pending_balance_credit_counter
+    );
+
     // Return the new account and its cryptographic keys
     let res = ConfTokenAccountRes {
+        token_account,
         token_account_kp,
         user_elgamal_kp: elgamal_kp,
         user_aes_kp: aes_kp,
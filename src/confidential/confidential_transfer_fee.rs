@@ -0,0 +1,129 @@
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+use spl_token_client::{
+    spl_token_2022::{
+        extension::{
+            confidential_transfer::ConfidentialTransferAccount,
+            confidential_transfer_fee::ConfidentialTransferFeeConfig, BaseStateWithExtensions,
+        },
+        solana_zk_sdk::encryption::{
+            auth_encryption::{AeCiphertext, AeKey},
+            elgamal::{ElGamalCiphertext, ElGamalKeypair},
+        },
+    },
+    token::Token,
+};
+
+use crate::{
+    error::{ConfidentialError, Result},
+    helper::{
+        fetch_recipient_elgamal_pubkey, format_token_amount, handle_token_response,
+        ConfidentialSender,
+    },
+};
+
+/// Moves the encrypted fees withheld in token accounts to the mint.
+/// Anyone can harvest; the fees stay encrypted under the withdraw withheld authority's key.
+///
+/// # Arguments
+/// * `token` - The SPL Token client.
+/// * `source_token_accounts` - Token accounts holding withheld confidential transfer fees.
+pub async fn harvest_withheld_fees<T: ConfidentialSender>(
+    token: &Token<T>,
+    source_token_accounts: &[Pubkey],
+) -> Result<()> {
+    println!("\n======== Harvesting Confidential Transfer Fees ========");
+    for source in source_token_accounts {
+        println!("- From: {}", source);
+    }
+
+    let sources: Vec<&Pubkey> = source_token_accounts.iter().collect();
+    let harvest_sig = token
+        .confidential_transfer_harvest_withheld_tokens_to_mint(&sources)
+        .await?;
+    handle_token_response(
+        &harvest_sig,
+        String::from("harvesting withheld fees to mint"),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Withdraws the encrypted fees withheld in the mint to the available balance of a
+/// confidential token account (the fee account).
+///
+/// # Arguments
+/// * `token` - The SPL Token client.
+/// * `authority` - The withdraw withheld authority of the mint.
+/// * `withdraw_withheld_elgamal_kp` - ElGamal keypair the withheld fees are encrypted under.
+/// * `destination_token_account` - Confidential token account receiving the fees.
+/// * `destination_aes_kp` - AE key of the destination, used to update its decryptable balance.
+///
+/// # Flow
+/// 1. Decrypts the withheld amount of the mint with the withdraw withheld ElGamal keypair.
+/// 2. Computes the new decryptable available balance of the destination.
+/// 3. Withdraws the withheld fees, proving the re-encryption under the destination's
+///    ElGamal pubkey with an equality proof.
+///
+/// # Returns
+/// * `u64` - The withdrawn fee amount in base units.
+pub async fn withdraw_withheld_fees<T: ConfidentialSender>(
+    token: &Token<T>,
+    authority: &Keypair,
+    withdraw_withheld_elgamal_kp: &ElGamalKeypair,
+    destination_token_account: &Pubkey,
+    destination_aes_kp: &AeKey,
+) -> Result<u64> {
+    let mint = token.get_mint_info().await?;
+    let decimals = mint.base.decimals;
+    let decryption_error = ConfidentialError::Decryption;
+
+    // Decrypt the fees withheld in the mint
+    let fee_config = mint.get_extension::<ConfidentialTransferFeeConfig>()?;
+    let withheld_amount = ElGamalCiphertext::try_from(fee_config.withheld_amount)
+        .map_err(|e| decryption_error(format!("Malformed withheld amount ciphertext: {e}")))?;
+    let withheld_amount = withdraw_withheld_elgamal_kp
+        .secret()
+        .decrypt_u32(&withheld_amount)
+        .ok_or_else(|| decryption_error(String::from("Failed to decrypt withheld amount")))?;
+
+    // The fees are added to the destination's available balance
+    let destination_elgamal_pubkey =
+        fetch_recipient_elgamal_pubkey(token, destination_token_account).await?;
+    let destination = token.get_account_info(destination_token_account).await?;
+    let destination_extension = destination.get_extension::<ConfidentialTransferAccount>()?;
+    let available_balance = AeCiphertext::try_from(
+        destination_extension.decryptable_available_balance,
+    )
+    .map_err(|e| decryption_error(format!("Malformed available balance ciphertext: {e}")))?;
+    let available_balance = destination_aes_kp
+        .decrypt(&available_balance)
+        .ok_or_else(|| decryption_error(String::from("Failed to decrypt available balance")))?;
+    let new_available_balance = available_balance
+        .checked_add(withheld_amount)
+        .ok_or_else(|| ConfidentialError::Invalid(String::from("Available balance overflow")))?;
+
+    println!("\n======== Withdrawing Confidential Transfer Fees ========");
+    println!(
+        "- Amount: {} tokens",
+        format_token_amount(withheld_amount, decimals)
+    );
+    println!("- To: {}", destination_token_account);
+
+    // The equality proof is small enough to be verified in the same transaction
+    let withdraw_sig = token
+        .confidential_transfer_withdraw_withheld_tokens_from_mint(
+            destination_token_account,
+            &authority.pubkey(),
+            None,
+            None,
+            withdraw_withheld_elgamal_kp,
+            &destination_elgamal_pubkey,
+            &destination_aes_kp.encrypt(new_available_balance).into(),
+            &[authority],
+        )
+        .await?;
+    handle_token_response(&withdraw_sig, String::from("withdrawing withheld fees")).await?;
+
+    Ok(withheld_amount)
+}
@@ -1,64 +1,112 @@
-use anyhow::{Ok, Result};
-use solana_sdk::{signature::Keypair, signer::Signer};
+use solana_sdk::{packet::PACKET_DATA_SIZE, pubkey::Pubkey, signature::Keypair, signer::Signer};
 use spl_token_client::{
-    client::ProgramRpcClientSendTransaction,
     spl_token_2022::{
+        self,
         extension::{
             confidential_transfer::{
-                account_info::TransferAccountInfo, ConfidentialTransferAccount,
+                account_info::TransferAccountInfo, instruction::transfer,
+                ConfidentialTransferAccount,
             },
+            confidential_transfer_fee::ConfidentialTransferFeeConfig,
+            transfer_fee::TransferFeeConfig,
             BaseStateWithExtensions,
         },
-        solana_zk_sdk::encryption::{auth_encryption::AeKey, elgamal::ElGamalKeypair},
+        solana_zk_sdk::encryption::{
+            auth_encryption::AeKey,
+            elgamal::{ElGamalKeypair, ElGamalPubkey},
+        },
     },
     token::{ProofAccountWithCiphertext, Token},
 };
+use spl_token_confidential_transfer_proof_extraction::instruction::ProofLocation;
+use spl_token_confidential_transfer_proof_generation::{
+    transfer::TransferProofData, transfer_with_fee::TransferWithFeeProofData,
+};
+
+use std::{num::NonZeroI8, time::Instant};
+use tokio::task;
+
+use super::ProofContextAccounts;
+use crate::{
+    error::{ConfidentialError, Result},
+    helper::{
+        fetch_auditor_elgamal_pubkey, fetch_current_epoch, fetch_recipient_elgamal_pubkey,
+        format_token_amount, handle_token_response, transaction_size, ConfidentialSender,
+    },
+};
 
-use crate::confidential::apply_pending;
+/// The inline equality and validity proof instructions directly follow the `Transfer` instruction.
+const EQUALITY_PROOF_INSTRUCTION_OFFSET: NonZeroI8 = NonZeroI8::new(1).unwrap();
+const VALIDITY_PROOF_INSTRUCTION_OFFSET: NonZeroI8 = NonZeroI8::new(2).unwrap();
 
-/// Performs a confidential token transfer using ZK proofs and applies the pending balance to the recipient.
+/// Performs a confidential token transfer using ZK proofs.
+///
+/// Only the recipient's token account address is needed: the recipient's ElGamal pubkey is
+/// read from its on-chain `ConfidentialTransferAccount` extension. The transferred amount lands
+/// in the recipient's pending balance, which the recipient applies separately (`apply_pending`).
+/// Mints with a transfer fee are handled by [`transfer_tokens_with_fee`].
 ///
 /// # Arguments
-/// * `amount` - The amount to transfer (in base units, e.g., 1 = 1 token if decimals=0)
+/// * `amount` - The amount to transfer (in base units, e.g., 1_000_000 = 1 token if decimals=6)
 /// * `token` - The SPL Token client
-/// * `sender_token_kp` - Sender's confidential token account keypair
+/// * `sender_token_account` - Sender's confidential token account
 /// * `sender_elgamal_kp` - Sender's ElGamal keypair for encryption
 /// * `sender_aes_kp` - Sender's AE key for encryption
 /// * `sender_kp` - Sender's main keypair (authority)
-/// * `recipint_kp` - Recipient's main keypair
-/// * `recipt_elgmal_kp` - Recipient's ElGamal keypair
-/// * `recipt_aes_kp` - Recipient's AE key
-/// * `recipint_token_kp` - Recipient's confidential token account keypair
+/// * `recipient_token_account` - Recipient's confidential token account
 ///
 /// # Flow
-/// 1. Generates three ZK proofs: equality, validity, and range.
-/// 2. Creates context state accounts for each proof.
-/// 3. Executes the confidential transfer referencing the proof accounts.
-/// 4. Applies the pending balance to the recipient's available balance.
-/// 5. Closes all proof context state accounts to reclaim rent.
-pub async fn transfer_tokens(
+/// 1. Reads the recipient's ElGamal pubkey from the recipient token account.
+/// 2. Generates three ZK proofs on the blocking thread pool: equality, validity, and range.
+///    If the mint has an auditor, the transfer amount is also encrypted under the auditor's
+///    ElGamal pubkey.
+/// 3. Creates context state accounts for the three proofs concurrently.
+/// 4. Executes the confidential transfer referencing the proof accounts.
+/// 5. Closes all proof context state accounts to reclaim rent and prints the time taken by
+///    each phase.
+pub async fn transfer_tokens<T: ConfidentialSender>(
     amount: u64,
-    token: &Token<ProgramRpcClientSendTransaction>,
+    token: &Token<T>,
 
-    sender_token_kp: &Keypair,
+    sender_token_account: &Pubkey,
     sender_elgamal_kp: &ElGamalKeypair,
     sender_aes_kp: &AeKey,
     sender_kp: &Keypair,
 
-    recipint_kp: &Keypair,
-    recipt_elgmal_kp: &ElGamalKeypair,
-    recipt_aes_kp: &AeKey,
-    recipint_token_kp: &Keypair,
+    recipient_token_account: &Pubkey,
 ) -> Result<()> {
+    // Mints with a transfer fee need the fee proofs as well
+    let mint = token.get_mint_info().await?;
+    if mint.get_extension::<TransferFeeConfig>().is_ok() {
+        return transfer_tokens_with_fee(
+            amount,
+            token,
+            sender_token_account,
+            sender_elgamal_kp,
+            sender_aes_kp,
+            sender_kp,
+            recipient_token_account,
+        )
+        .await;
+    }
+
     // Generate three types of zero-knowledge proofs to convince the on-chain program that the transfer is correct without revealing any amounts.
     // 1) Equality Proof: Proves the transferred amount is the same for sender and recipient.
     // 2) Ciphertext Validity Proof: Proves the ciphertexts are valid encryptions.
     // 3) Range Proof: Proves the transferred amount is within a valid range.
 
-    let transfer_amount = amount * 10u64.pow(6); // Adjust for token decimals
+    // Read the decimals from the mint (only used to display the amount)
+    let decimals = mint.base.decimals;
+
+    // Read the recipient's ElGamal pubkey from its confidential token account
+    let recipient_elgamal_pubkey =
+        fetch_recipient_elgamal_pubkey(token, recipient_token_account).await?;
+
+    // Read the auditor from the mint, the transfer amount must also be encrypted for it
+    let auditor_elgamal_pubkey = fetch_auditor_elgamal_pubkey(token).await?;
 
     // Get the token account data (contains both token base account and confidential account)
-    let token_account = token.get_account_info(&sender_token_kp.pubkey()).await?;
+    let token_account = token.get_account_info(sender_token_account).await?;
 
     // Extract the confidential transfer extension data from the token account data
     let extension_data = token_account.get_extension::<ConfidentialTransferAccount>()?;
@@ -66,139 +114,538 @@ pub async fn transfer_tokens(
     // Create TransferAccountInfo from the extension data to perform transfer instructions
     let transfer_account_info = TransferAccountInfo::new(extension_data);
 
-    // Generate the proof data for the transfer (all ZKPs required for a confidential transfer)
-    let transfer_proof_data = transfer_account_info.generate_split_transfer_proof_data(
-        transfer_amount,
-        &sender_elgamal_kp,
-        &sender_aes_kp,
-        recipt_elgmal_kp.pubkey(),
-        None, // auditor ElGamal public key (none if no auditor)
-    )?;
-
     println!("\n======== Preparing Confidential Transfer ========");
     println!("Transfer Details:");
-    println!("- Amount: {} tokens", amount);
-    println!("- From: {}", sender_token_kp.pubkey());
-    println!("- To: {}", recipint_token_kp.pubkey());
+    println!("- Amount: {} tokens", format_token_amount(amount, decimals));
+    println!("- From: {}", sender_token_account);
+    println!("- To: {}", recipient_token_account);
+    if auditor_elgamal_pubkey.is_some() {
+        println!("- Auditor: amount also encrypted for the mint's auditor");
+    }
 
     println!("\nGenerating Zero-Knowledge Proofs...");
+
+    // Generate the proof data for the transfer (all ZKPs required for a confidential transfer)
+    let phase_start = Instant::now();
+    let transfer_proof_data = generate_transfer_proof_data(
+        transfer_account_info,
+        amount,
+        sender_elgamal_kp,
+        sender_aes_kp,
+        recipient_elgamal_pubkey,
+        auditor_elgamal_pubkey,
+    )
+    .await?;
+    let mut timings = vec![("Proof generation", phase_start.elapsed())];
+
     println!("Creating proof context state accounts:");
 
-    // Create context state accounts for each proof
-    let equality_proof_context_state_keypair = Keypair::new(); // Equality Proof
-    let ciphertext_validity_proof_context_state_keypair = Keypair::new(); // Validity Proof
-    let range_proof_context_state_keypair = Keypair::new(); // Range Proof
+    // The proof accounts are closed whether the transfer succeeds or not
+    let proof_accounts = ProofContextAccounts::new(token, sender_kp);
+    let result = async {
+        // The proofs are independent, so their context state accounts are created concurrently.
+        // Every creation runs to completion before the first error is returned, so none is
+        // still in flight when the accounts are closed
+        println!("1. Creating Equality Proof (proves transferred amount is the same for sender and recipient)...");
+        println!(
+            "2. Creating Ciphertext Validity Proof (proves the encrypted amounts are valid)..."
+        );
+        println!("3. Creating Range Proof (proves the transfer amount is within valid range)...");
+        let phase_start = Instant::now();
+        let created = tokio::join!(
+            proof_accounts.create(&transfer_proof_data.equality_proof_data, false),
+            proof_accounts.create(
+                &transfer_proof_data
+                    .ciphertext_validity_proof_data_with_ciphertext
+                    .proof_data,
+                false,
+            ),
+            proof_accounts.create(&transfer_proof_data.range_proof_data, true),
+        );
+        let (
+            equality_proof_context_state,
+            ciphertext_validity_proof_context_state,
+            range_proof_context_state,
+        ) = (created.0?, created.1?, created.2?);
+        timings.push(("Proof context state accounts", phase_start.elapsed()));
+        println!("   ✓ Equality, ciphertext validity and range proofs created");
 
-    // Create context state account for equality proof
-    println!("1. Creating Equality Proof (proves transferred amount is the same for sender and recipient)...");
-    token
-        .confidential_transfer_create_context_state_account(
-            &equality_proof_context_state_keypair.pubkey(),
-            &sender_kp.pubkey(),
-            &transfer_proof_data.equality_proof_data, // equality proof data
-            false,
-            &[&equality_proof_context_state_keypair],
-        )
-        .await?;
-    println!("   ✓ Equality proof created");
-
-    // Create context state account for ciphertext validity proof
-    println!("2. Creating Ciphertext Validity Proof (proves the encrypted amounts are valid)...");
-    token
-        .confidential_transfer_create_context_state_account(
-            &ciphertext_validity_proof_context_state_keypair.pubkey(),
-            &sender_kp.pubkey(),
-            &transfer_proof_data
+        // Execute the confidential transfer
+        println!("Executing confidential transfer transaction...");
+        let phase_start = Instant::now();
+        let ciphertext_validity_proof_account_with_ciphertext = ProofAccountWithCiphertext {
+            context_state_account: ciphertext_validity_proof_context_state,
+            ciphertext_lo: transfer_proof_data
                 .ciphertext_validity_proof_data_with_ciphertext
-                .proof_data,
-            false,
-            &[&ciphertext_validity_proof_context_state_keypair],
-        )
-        .await?;
-    println!("   ✓ Ciphertext validity proof created");
-
-    // Create context state account for range proof
-    println!("3. Creating Range Proof (proves the transfer amount is within valid range)...");
-    token
-        .confidential_transfer_create_context_state_account(
-            &range_proof_context_state_keypair.pubkey(),
-            &sender_kp.pubkey(),
-            &transfer_proof_data.range_proof_data,
-            true,
-            &[&range_proof_context_state_keypair],
+                .ciphertext_lo,
+            ciphertext_hi: transfer_proof_data
+                .ciphertext_validity_proof_data_with_ciphertext
+                .ciphertext_hi,
+        };
+
+        let transfer_signature = token
+            .confidential_transfer_transfer(
+                sender_token_account,
+                recipient_token_account,
+                &sender_kp.pubkey(),
+                Some(&equality_proof_context_state),
+                Some(&ciphertext_validity_proof_account_with_ciphertext),
+                Some(&range_proof_context_state),
+                amount,
+                None,
+                sender_elgamal_kp,
+                sender_aes_kp,
+                &recipient_elgamal_pubkey,
+                auditor_elgamal_pubkey.as_ref(),
+                &[sender_kp],
+            )
+            .await?;
+        timings.push(("Transfer", phase_start.elapsed()));
+
+        handle_token_response(&transfer_signature, String::from("confidential transfer")).await
+    }
+    .await;
+
+    // Close all proof context state accounts to reclaim rent. The timings are reported
+    // whatever the outcome, they also help to find where a failing transfer got stuck
+    let phase_start = Instant::now();
+    let result = proof_accounts.close_after(result).await;
+    timings.push(("Closing proof accounts", phase_start.elapsed()));
+
+    println!("Timings:");
+    for (phase, elapsed) in &timings {
+        println!("- {}: {:.2?}", phase, elapsed);
+    }
+    result?;
+    println!("Note: The recipient must apply their pending balance to use the received tokens");
+
+    Ok(())
+}
+
+/// Performs a confidential transfer with the equality and ciphertext validity proofs
+/// verified inline, in the same transaction as the transfer instruction, where they fit.
+///
+/// The range proof is too large to be verified inline, so it always needs a context state
+/// account. Both inline proofs together usually exceed the packet size, so the transaction
+/// size is checked before anything is sent: first with both proofs inline, then with only
+/// the equality proof inline and the validity proof in a context state account. When neither
+/// fits (or the mint charges a transfer fee), the transfer falls back to [`transfer_tokens`].
+///
+/// # Arguments
+/// * `amount` - The amount to transfer (in base units)
+/// * `token` - The SPL Token client
+/// * `sender_token_account` - Sender's confidential token account
+/// * `sender_elgamal_kp` - Sender's ElGamal keypair for encryption
+/// * `sender_aes_kp` - Sender's AE key for encryption
+/// * `sender_kp` - Sender's main keypair (authority)
+/// * `recipient_token_account` - Recipient's confidential token account
+///
+/// # Flow
+/// 1. Generates the equality, validity and range proofs, exactly like [`transfer_tokens`].
+/// 2. Builds the transfer instruction followed by the inline proof instructions (instruction
+///    offsets 1 and 2) and picks the first layout whose transaction fits.
+/// 3. Creates context state accounts for the proofs that are not inline.
+/// 4. Sends the transfer and the inline proofs in one transaction.
+/// 5. Closes the proof context state accounts to reclaim rent.
+///
+/// # Returns
+/// * `usize` - Number of proofs verified inline (`0` after a fallback).
+pub async fn transfer_tokens_with_inline_proofs<T: ConfidentialSender>(
+    amount: u64,
+    token: &Token<T>,
+
+    sender_token_account: &Pubkey,
+    sender_elgamal_kp: &ElGamalKeypair,
+    sender_aes_kp: &AeKey,
+    sender_kp: &Keypair,
+
+    recipient_token_account: &Pubkey,
+) -> Result<usize> {
+    let fallback = || {
+        transfer_tokens(
+            amount,
+            token,
+            sender_token_account,
+            sender_elgamal_kp,
+            sender_aes_kp,
+            sender_kp,
+            recipient_token_account,
         )
-        .await?;
-    println!("   ✓ Range proof created");
-
-    // Execute the confidential transfer
-    println!("Executing confidential transfer transaction...");
-    let ciphertext_validity_proof_account_with_ciphertext = ProofAccountWithCiphertext {
-        context_state_account: ciphertext_validity_proof_context_state_keypair.pubkey(),
-        ciphertext_lo: transfer_proof_data
-            .ciphertext_validity_proof_data_with_ciphertext
-            .ciphertext_lo,
-        ciphertext_hi: transfer_proof_data
-            .ciphertext_validity_proof_data_with_ciphertext
-            .ciphertext_hi,
     };
 
-    let transfer_signature = token
-        .confidential_transfer_transfer(
-            &sender_token_kp.pubkey(),
-            &recipint_token_kp.pubkey(),
-            &sender_kp.pubkey(),
-            Some(&equality_proof_context_state_keypair.pubkey()),
-            Some(&ciphertext_validity_proof_account_with_ciphertext),
-            Some(&range_proof_context_state_keypair.pubkey()),
-            transfer_amount,
-            None,
-            &sender_elgamal_kp,
-            &sender_aes_kp,
-            recipt_elgmal_kp.pubkey(),
-            None,
-            &[&sender_kp],
-        )
-        .await?;
+    // The fee proofs of a transfer with fee never fit next to the others
+    let mint = token.get_mint_info().await?;
+    if mint.get_extension::<TransferFeeConfig>().is_ok() {
+        println!("Mint charges a transfer fee, using proof context state accounts");
+        fallback().await?;
+        return Ok(0);
+    }
+    let decimals = mint.base.decimals;
 
-    println!("Confidential Transfer Signature: {}", transfer_signature);
+    let recipient_elgamal_pubkey =
+        fetch_recipient_elgamal_pubkey(token, recipient_token_account).await?;
+    let auditor_elgamal_pubkey = fetch_auditor_elgamal_pubkey(token).await?;
 
-    // Apply the pending balance to the recipient's available balance
-    apply_pending(
-        &token,
-        &recipint_kp,
-        &recipt_elgmal_kp,
-        &recipt_aes_kp,
-        &recipint_token_kp,
+    let token_account = token.get_account_info(sender_token_account).await?;
+    let extension_data = token_account.get_extension::<ConfidentialTransferAccount>()?;
+    let transfer_account_info = TransferAccountInfo::new(extension_data);
+
+    let new_decryptable_available_balance = transfer_account_info
+        .new_decryptable_available_balance(amount, sender_aes_kp)
+        .map_err(|_| ConfidentialError::Decryption(String::from("available balance")))?
+        .into();
+
+    // The range proof commits to the same openings as the other two proofs, so all three
+    // come from a single proof generation
+    let TransferProofData {
+        equality_proof_data,
+        ciphertext_validity_proof_data_with_ciphertext,
+        range_proof_data,
+    } = generate_transfer_proof_data(
+        transfer_account_info,
+        amount,
+        sender_elgamal_kp,
+        sender_aes_kp,
+        recipient_elgamal_pubkey,
+        auditor_elgamal_pubkey,
     )
     .await?;
 
-    // Close all proof context state accounts to reclaim rent
-    println!("Closing all proof context state account...");
-    token
-        .confidential_transfer_close_context_state_account(
-            &equality_proof_context_state_keypair.pubkey(),
+    // The validity proof is inline without a context state account
+    let transfer_ixs = |ciphertext_validity_proof_context_state: Option<&Pubkey>,
+                        range_proof_context_state: &Pubkey| {
+        let validity_proof_location = match ciphertext_validity_proof_context_state {
+            Some(context_state_account) => {
+                ProofLocation::ContextStateAccount(context_state_account)
+            }
+            None => ProofLocation::InstructionOffset(
+                VALIDITY_PROOF_INSTRUCTION_OFFSET,
+                &ciphertext_validity_proof_data_with_ciphertext.proof_data,
+            ),
+        };
+        transfer(
+            &spl_token_2022::ID,
+            sender_token_account,
+            token.get_address(),
+            recipient_token_account,
+            &new_decryptable_available_balance,
+            &ciphertext_validity_proof_data_with_ciphertext.ciphertext_lo,
+            &ciphertext_validity_proof_data_with_ciphertext.ciphertext_hi,
             &sender_kp.pubkey(),
-            &sender_kp.pubkey(),
-            &[&sender_kp],
+            &[],
+            ProofLocation::InstructionOffset(
+                EQUALITY_PROOF_INSTRUCTION_OFFSET,
+                &equality_proof_data,
+            ),
+            validity_proof_location,
+            ProofLocation::ContextStateAccount(range_proof_context_state),
         )
-        .await?;
-    token
-        .confidential_transfer_close_context_state_account(
-            &ciphertext_validity_proof_context_state_keypair.pubkey(),
-            &sender_kp.pubkey(),
-            &sender_kp.pubkey(),
-            &[&sender_kp],
-        )
-        .await?;
-    token
-        .confidential_transfer_close_context_state_account(
-            &range_proof_context_state_keypair.pubkey(),
-            &sender_kp.pubkey(),
-            &sender_kp.pubkey(),
-            &[&sender_kp],
+    };
+
+    // Nothing has been sent yet, so a transaction that is too large costs nothing. The size
+    // does not depend on the context state addresses, so distinct placeholders stand in for
+    // them. The fee payer of `token` may differ from the sender, so it is counted as an extra
+    // signer.
+    let (validity_placeholder, range_placeholder) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut layout = None;
+    for validity_inline in [true, false] {
+        let size = transaction_size(
+            &transfer_ixs(
+                (!validity_inline).then_some(&validity_placeholder),
+                &range_placeholder,
+            )?,
+            &Pubkey::new_unique(),
+        );
+        if size <= PACKET_DATA_SIZE {
+            layout = Some((validity_inline, size));
+            break;
+        }
+        println!(
+            "{} inline need a {} byte transaction (limit {})",
+            if validity_inline {
+                "Equality and validity proofs"
+            } else {
+                "Equality proof"
+            },
+            size,
+            PACKET_DATA_SIZE
+        );
+    }
+    let Some((validity_inline, size)) = layout else {
+        println!("Inline proofs do not fit, using proof context state accounts");
+        fallback().await?;
+        return Ok(0);
+    };
+
+    println!("\n======== Preparing Single-Transaction Confidential Transfer ========");
+    println!("Transfer Details:");
+    println!("- Amount: {} tokens", format_token_amount(amount, decimals));
+    println!("- From: {}", sender_token_account);
+    println!("- To: {}", recipient_token_account);
+    println!("- Transaction size: {} bytes", size);
+
+    let inline_proofs = if validity_inline { 2 } else { 1 };
+
+    // The proof accounts are closed whether the transfer succeeds or not
+    let proof_accounts = ProofContextAccounts::new(token, sender_kp);
+    let result = async {
+        let ciphertext_validity_proof_context_state = if validity_inline {
+            None
+        } else {
+            println!("\nCreating Ciphertext Validity Proof context state account...");
+            let context_state_account = proof_accounts
+                .create(
+                    &ciphertext_validity_proof_data_with_ciphertext.proof_data,
+                    false,
+                )
+                .await?;
+            println!("   ✓ Ciphertext validity proof created");
+            Some(context_state_account)
+        };
+
+        println!("\nCreating Range Proof context state account...");
+        let range_proof_context_state = proof_accounts.create(&range_proof_data, true).await?;
+        println!("   ✓ Range proof created");
+
+        println!(
+            "Executing transfer with {} inline proof(s)...",
+            inline_proofs
+        );
+        let transfer_ixs = transfer_ixs(
+            ciphertext_validity_proof_context_state.as_ref(),
+            &range_proof_context_state,
+        )?;
+        let transfer_signature = token.process_ixs(&transfer_ixs, &[sender_kp]).await?;
+        handle_token_response(&transfer_signature, String::from("confidential transfer")).await
+    }
+    .await;
+
+    proof_accounts.close_after(result).await?;
+    println!("Note: The recipient must apply their pending balance to use the received tokens");
+
+    Ok(inline_proofs)
+}
+
+/// Performs a confidential token transfer on a mint with a transfer fee.
+///
+/// The fee is deducted from the transferred amount and withheld, encrypted under the
+/// mint's withdraw withheld authority ElGamal pubkey, in the recipient's token account.
+///
+/// # Arguments
+/// * `amount` - The amount to transfer, including the fee (in base units)
+/// * `token` - The SPL Token client
+/// * `sender_token_account` - Sender's confidential token account
+/// * `sender_elgamal_kp` - Sender's ElGamal keypair for encryption
+/// * `sender_aes_kp` - Sender's AE key for encryption
+/// * `sender_kp` - Sender's main keypair (authority)
+/// * `recipient_token_account` - Recipient's confidential token account
+///
+/// # Flow
+/// 1. Reads the fee of the current epoch and the withdraw withheld authority from the mint.
+/// 2. Generates five ZK proofs: equality, transfer amount validity, fee sigma (the fee is
+///    the configured percentage of the amount, capped at the maximum fee), fee validity,
+///    and range.
+/// 3. Creates context state accounts for each proof.
+/// 4. Executes the confidential transfer with fee referencing the proof accounts.
+/// 5. Closes all proof context state accounts to reclaim rent.
+pub async fn transfer_tokens_with_fee<T: ConfidentialSender>(
+    amount: u64,
+    token: &Token<T>,
+
+    sender_token_account: &Pubkey,
+    sender_elgamal_kp: &ElGamalKeypair,
+    sender_aes_kp: &AeKey,
+    sender_kp: &Keypair,
+
+    recipient_token_account: &Pubkey,
+) -> Result<()> {
+    let mint = token.get_mint_info().await?;
+    let decimals = mint.base.decimals;
+
+    // The fee in effect for the current epoch
+    let epoch = fetch_current_epoch(token).await?;
+    let transfer_fee = *mint
+        .get_extension::<TransferFeeConfig>()?
+        .get_epoch_fee(epoch);
+    let fee_rate_basis_points = u16::from(transfer_fee.transfer_fee_basis_points);
+    let maximum_fee = u64::from(transfer_fee.maximum_fee);
+    let fee = transfer_fee.calculate_fee(amount).ok_or_else(|| {
+        ConfidentialError::Invalid(String::from("Transfer fee calculation overflow"))
+    })?;
+
+    // Withheld fees are encrypted under the withdraw withheld authority's ElGamal pubkey
+    let fee_config = mint
+        .get_extension::<ConfidentialTransferFeeConfig>()
+        .map_err(|_| {
+            ConfidentialError::Invalid(String::from(
+                "Mint charges a transfer fee but does not support confidential transfer fees",
+            ))
+        })?;
+    let withdraw_withheld_authority_elgamal_pubkey = ElGamalPubkey::try_from(
+        fee_config.withdraw_withheld_authority_elgamal_pubkey,
+    )
+    .map_err(|e| {
+        ConfidentialError::Invalid(format!(
+            "Invalid withdraw withheld authority ElGamal pubkey on mint: {e}"
+        ))
+    })?;
+
+    let recipient_elgamal_pubkey =
+        fetch_recipient_elgamal_pubkey(token, recipient_token_account).await?;
+    let auditor_elgamal_pubkey = fetch_auditor_elgamal_pubkey(token).await?;
+
+    let token_account = token.get_account_info(sender_token_account).await?;
+    let transfer_account_info =
+        TransferAccountInfo::new(token_account.get_extension::<ConfidentialTransferAccount>()?);
+
+    let TransferWithFeeProofData {
+        equality_proof_data,
+        transfer_amount_ciphertext_validity_proof_data_with_ciphertext,
+        percentage_with_cap_proof_data,
+        fee_ciphertext_validity_proof_data,
+        range_proof_data,
+    } = transfer_account_info.generate_split_transfer_with_fee_proof_data(
+        amount,
+        sender_elgamal_kp,
+        sender_aes_kp,
+        &recipient_elgamal_pubkey,
+        auditor_elgamal_pubkey.as_ref(),
+        &withdraw_withheld_authority_elgamal_pubkey,
+        fee_rate_basis_points,
+        maximum_fee,
+    )?;
+
+    println!("\n======== Preparing Confidential Transfer With Fee ========");
+    println!("Transfer Details:");
+    println!("- Amount: {} tokens", format_token_amount(amount, decimals));
+    println!(
+        "- Fee: {} tokens ({} basis points, maximum {} tokens)",
+        format_token_amount(fee, decimals),
+        fee_rate_basis_points,
+        format_token_amount(maximum_fee, decimals)
+    );
+    println!("- From: {}", sender_token_account);
+    println!("- To: {}", recipient_token_account);
+    if auditor_elgamal_pubkey.is_some() {
+        println!("- Auditor: amount also encrypted for the mint's auditor");
+    }
+
+    println!("\nGenerating Zero-Knowledge Proofs...");
+    println!("Creating proof context state accounts:");
+
+    // The proof accounts are closed whether the transfer succeeds or not
+    let proof_accounts = ProofContextAccounts::new(token, sender_kp);
+    let result = async {
+        println!("1. Creating Equality Proof (proves the remaining balance is correct)...");
+        let equality_proof_context_state =
+            proof_accounts.create(&equality_proof_data, false).await?;
+        println!("   ✓ Equality proof created");
+
+        println!(
+            "2. Creating Ciphertext Validity Proof (proves the encrypted amounts are valid)..."
+        );
+        let transfer_amount_ciphertext_validity_proof_context_state = proof_accounts
+            .create(
+                &transfer_amount_ciphertext_validity_proof_data_with_ciphertext.proof_data,
+                false,
+            )
+            .await?;
+        println!("   ✓ Ciphertext validity proof created");
+
+        println!("3. Creating Fee Sigma Proof (proves the fee matches the fee rate and cap)...");
+        let percentage_with_cap_proof_context_state = proof_accounts
+            .create(&percentage_with_cap_proof_data, false)
+            .await?;
+        println!("   ✓ Fee sigma proof created");
+
+        println!(
+            "4. Creating Fee Ciphertext Validity Proof (proves the encrypted fee is valid)..."
+        );
+        let fee_ciphertext_validity_proof_context_state = proof_accounts
+            .create(&fee_ciphertext_validity_proof_data, false)
+            .await?;
+        println!("   ✓ Fee ciphertext validity proof created");
+
+        println!(
+            "5. Creating Range Proof (proves the amounts and the fee are within valid range)..."
+        );
+        let range_proof_context_state = proof_accounts
+            .create_range_proof_u256(&range_proof_data)
+            .await?;
+        println!("   ✓ Range proof created");
+
+        // Execute the confidential transfer with fee
+        println!("Executing confidential transfer with fee transaction...");
+        let transfer_amount_ciphertext_validity_proof_account_with_ciphertext =
+            ProofAccountWithCiphertext {
+                context_state_account: transfer_amount_ciphertext_validity_proof_context_state,
+                ciphertext_lo: transfer_amount_ciphertext_validity_proof_data_with_ciphertext
+                    .ciphertext_lo,
+                ciphertext_hi: transfer_amount_ciphertext_validity_proof_data_with_ciphertext
+                    .ciphertext_hi,
+            };
+
+        let transfer_signature = token
+            .confidential_transfer_transfer_with_fee(
+                sender_token_account,
+                recipient_token_account,
+                &sender_kp.pubkey(),
+                Some(&equality_proof_context_state),
+                Some(&transfer_amount_ciphertext_validity_proof_account_with_ciphertext),
+                Some(&percentage_with_cap_proof_context_state),
+                Some(&fee_ciphertext_validity_proof_context_state),
+                Some(&range_proof_context_state),
+                amount,
+                Some(transfer_account_info),
+                sender_elgamal_kp,
+                sender_aes_kp,
+                &recipient_elgamal_pubkey,
+                auditor_elgamal_pubkey.as_ref(),
+                &withdraw_withheld_authority_elgamal_pubkey,
+                fee_rate_basis_points,
+                maximum_fee,
+                &[sender_kp],
+            )
+            .await?;
+
+        handle_token_response(
+            &transfer_signature,
+            String::from("confidential transfer with fee"),
         )
-        .await?;
-    println!("Closed all context state accounts");
+        .await
+    }
+    .await;
+
+    proof_accounts.close_after(result).await?;
+    println!("Note: The recipient must apply their pending balance to use the received tokens");
 
     Ok(())
 }
+
+/// Generates the equality, ciphertext validity and range proofs of a transfer.
+///
+/// Proof generation is CPU bound, so it runs on the blocking thread pool instead of holding
+/// up the async executor.
+async fn generate_transfer_proof_data(
+    transfer_account_info: TransferAccountInfo,
+    amount: u64,
+    sender_elgamal_kp: &ElGamalKeypair,
+    sender_aes_kp: &AeKey,
+    recipient_elgamal_pubkey: ElGamalPubkey,
+    auditor_elgamal_pubkey: Option<ElGamalPubkey>,
+) -> Result<TransferProofData> {
+    let (elgamal_kp, aes_kp) = (sender_elgamal_kp.clone(), sender_aes_kp.clone());
+    let transfer_proof_data = task::spawn_blocking(move || {
+        transfer_account_info.generate_split_transfer_proof_data(
+            amount,
+            &elgamal_kp,
+            &aes_kp,
+            &recipient_elgamal_pubkey,
+            auditor_elgamal_pubkey.as_ref(), // auditor ElGamal public key (none if no auditor)
+        )
+    })
+    .await
+    .map_err(|e| ConfidentialError::ProofGeneration(e.to_string()))??;
+
+    Ok(transfer_proof_data)
+}
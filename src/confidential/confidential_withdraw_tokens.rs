@@ -1,10 +1,10 @@
-use anyhow::{Ok, Result};
 use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
 use spl_token_client::{
-    client::ProgramRpcClientSendTransaction,
     spl_token_2022::{
         extension::{
-            confidential_transfer::{account_info::WithdrawAccountInfo, ConfidentialTransferAccount},
+            confidential_transfer::{
+                account_info::WithdrawAccountInfo, ConfidentialTransferAccount,
+            },
             BaseStateWithExtensions,
         },
         solana_zk_sdk::encryption::{auth_encryption::AeKey, elgamal::ElGamalKeypair},
@@ -13,7 +13,11 @@ use spl_token_client::{
 };
 use spl_token_confidential_transfer_proof_generation::withdraw::WithdrawProofData;
 
-use crate::helper::handle_token_response;
+use super::ProofContextAccounts;
+use crate::{
+    error::Result,
+    helper::{format_token_amount, handle_token_response, ConfidentialSender},
+};
 
 /// Withdraws tokens from a confidential account, proving correctness with ZK proofs.
 ///
@@ -21,7 +25,7 @@ use crate::helper::handle_token_response;
 /// * `token_pubkey` - The confidential token account public key.
 /// * `elgmal_kp` - ElGamal keypair for confidential encryption.
 /// * `aes_key` - AE key for confidential encryption.
-/// * `amount` - Amount to withdraw (in base units).
+/// * `amount` - Amount to withdraw in base units (the mint's decimals are read from the mint).
 /// * `token` - The SPL Token client.
 /// * `user_kp` - The user's main keypair (authority).
 ///
@@ -31,14 +35,17 @@ use crate::helper::handle_token_response;
 /// 3. Creates context state accounts for each proof.
 /// 4. Executes the confidential withdrawal referencing the proof accounts.
 /// 5. Closes all proof context state accounts to reclaim rent.
-pub async fn withdraw_tokens(
+pub async fn withdraw_tokens<T: ConfidentialSender>(
     token_pubkey: &Pubkey,
     elgmal_kp: &ElGamalKeypair,
     aes_key: &AeKey,
     amount: u64,
-    token: &Token<ProgramRpcClientSendTransaction>,
+    token: &Token<T>,
     user_kp: &Keypair,
 ) -> Result<()> {
+    // Read the decimals from the mint, the withdraw instruction checks them
+    let decimals = token.get_mint_info().await?.base.decimals;
+
     // Get the token account data to access the confidential transfer extension
     let token_accountinfo = token.get_account_info(token_pubkey).await?;
     let extension_data = token_accountinfo.get_extension::<ConfidentialTransferAccount>()?;
@@ -46,94 +53,52 @@ pub async fn withdraw_tokens(
     // Prepare withdrawal account info for proof generation
     let withdraw_accountinfo = WithdrawAccountInfo::new(extension_data);
 
-    // Create keypairs for the proof context state accounts
-    let equality_proof_context_state_keypair = Keypair::new();
-    let equality_proof_context_state_pubkey = equality_proof_context_state_keypair.pubkey();
-    let range_proof_context_state_keypair = Keypair::new();
-    let range_proof_context_state_pubkey = range_proof_context_state_keypair.pubkey();
-
     // Generate the ZK proof data for withdrawal (equality and range proofs)
     let WithdrawProofData {
         equality_proof_data,
         range_proof_data,
     } = withdraw_accountinfo.generate_proof_data(
-        amount * 10u64.pow(6), // Amount to withdraw (adjust for decimals)
-        &elgmal_kp,            // ElGamal keypair for encryption
-        &aes_key,              // AES key for encryption
+        amount,    // Amount to withdraw (in base units)
+        elgmal_kp, // ElGamal keypair for encryption
+        aes_key,   // AES key for encryption
     )?;
 
-    // Create context state account for equality proof
-    println!("Create equality proof context state account");
-    let equality_proof_signature = token
-        .confidential_transfer_create_context_state_account(
-            &equality_proof_context_state_pubkey,
-            &user_kp.pubkey(),
-            &equality_proof_data,
-            false,
-            &[&equality_proof_context_state_keypair],
-        )
-        .await?;
-    println!(
-        "Equality Proof Context State Account Signature: {}",
-        equality_proof_signature
-    );
+    // The proof accounts are closed whether the withdrawal succeeds or not
+    let proof_accounts = ProofContextAccounts::new(token, user_kp);
+    let result = async {
+        // Create context state account for equality proof
+        println!("Create equality proof context state account");
+        let equality_proof_context_state_pubkey =
+            proof_accounts.create(&equality_proof_data, false).await?;
 
-    // Create context state account for range proof
-    println!("Create range proof context state account");
-    let range_proof_signature = token
-        .confidential_transfer_create_context_state_account(
-            &range_proof_context_state_pubkey,
-            &user_kp.pubkey(),
-            &range_proof_data,
-            true, // True: split account creation and proof verification for large proofs
-            &[&range_proof_context_state_keypair],
-        )
-        .await?;
-    println!(
-        "Range Proof Context State Account Signature: {}",
-        range_proof_signature
-    );
+        // Create context state account for range proof
+        println!("Create range proof context state account");
+        let range_proof_context_state_pubkey = proof_accounts
+            .create(&range_proof_data, true) // True: split account creation and proof verification for large proofs
+            .await?;
 
-    // Execute the confidential withdrawal referencing the proof accounts
-    println!("\n======== Preparing Confidential Withdraw ========");
-    let withdraw_sig = token
-        .confidential_transfer_withdraw(
-            token_pubkey,
-            &user_kp.pubkey(),
-            Some(&equality_proof_context_state_pubkey),
-            Some(&range_proof_context_state_pubkey),
-            amount * 10u64.pow(6), // Withdraw amount (adjust for decimals)
-            6,                     // Token decimals
-            Some(withdraw_accountinfo),
-            &elgmal_kp,
-            &aes_key,
-            &[&user_kp],
-        )
-        .await?;
+        // Execute the confidential withdrawal referencing the proof accounts
+        println!("\n======== Preparing Confidential Withdraw ========");
+        println!("- Amount: {} tokens", format_token_amount(amount, decimals));
+        let withdraw_sig = token
+            .confidential_transfer_withdraw(
+                token_pubkey,
+                &user_kp.pubkey(),
+                Some(&equality_proof_context_state_pubkey),
+                Some(&range_proof_context_state_pubkey),
+                amount,   // Withdraw amount (in base units)
+                decimals, // Token decimals
+                Some(withdraw_accountinfo),
+                elgmal_kp,
+                aes_key,
+                &[user_kp],
+            )
+            .await?;
 
-    handle_token_response(&withdraw_sig, String::from("confidential withdraw amount")).await?;
+        handle_token_response(&withdraw_sig, String::from("confidential withdraw amount")).await
+    }
+    .await;
 
     // Close all proof context state accounts to reclaim rent
-    println!("Closing all proof context state account...");
-    token
-        .confidential_transfer_close_context_state_account(
-            &equality_proof_context_state_pubkey,
-            &user_kp.pubkey(),
-            &user_kp.pubkey(),
-            &[&user_kp],
-        )
-        .await?;
-
-    token
-        .confidential_transfer_close_context_state_account(
-            &range_proof_context_state_pubkey,
-            &user_kp.pubkey(),
-            &user_kp.pubkey(),
-            &[&user_kp],
-        )
-        .await?;
-
-    println!("Closed all context state accounts");
-
-    Ok(())
+    proof_accounts.close_after(result).await
 }
@@ -1,13 +1,31 @@
 pub mod apply_pending_balance;
+pub mod confidential_approve_account;
+pub mod confidential_audit;
+pub mod confidential_credits;
 pub mod confidential_deposit_token;
+pub mod confidential_empty_account;
 pub mod confidential_mint;
+pub mod confidential_mint_burn;
+pub mod confidential_proof_accounts;
+pub mod confidential_recover_keys;
+pub mod confidential_rotate_key;
 pub mod confidential_token_account;
+pub mod confidential_transfer_fee;
 pub mod confidential_transfer_tokens;
 pub mod confidential_withdraw_tokens;
 
 pub use apply_pending_balance::*;
+pub use confidential_approve_account::*;
+pub use confidential_audit::*;
+pub use confidential_credits::*;
 pub use confidential_deposit_token::*;
+pub use confidential_empty_account::*;
 pub use confidential_mint::*;
+pub use confidential_mint_burn::*;
+pub use confidential_proof_accounts::*;
+pub use confidential_recover_keys::*;
+pub use confidential_rotate_key::*;
 pub use confidential_token_account::*;
+pub use confidential_transfer_fee::*;
 pub use confidential_transfer_tokens::*;
 pub use confidential_withdraw_tokens::*;
@@ -0,0 +1,96 @@
+use anyhow::{anyhow, Ok, Result};
+use solana_cli_config::{Config, ConfigInput, CONFIG_FILE};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use std::{path::Path, sync::Arc};
+
+// =================== Constants ===================
+
+/// RPC endpoint used when neither `--url` nor a config file sets one (a local validator).
+pub const DEFAULT_RPC_URL: &str = "http://localhost:8899";
+
+// =================== Structs ===================
+
+/// RPC endpoint, WebSocket endpoint and commitment level the tool talks to.
+#[derive(Debug, Clone)]
+pub struct RpcConfig {
+    pub url: String,    // JSON RPC endpoint (also used by the SPL Token client)
+    pub ws_url: String, // WebSocket endpoint for account subscriptions
+    pub commitment: CommitmentConfig, // Commitment level of reads and confirmations
+}
+
+// =================== RPC Config ===================
+
+impl RpcConfig {
+    /// Resolves the endpoints and the commitment level, preferring the command line flags,
+    /// then the config file, then the defaults.
+    ///
+    /// # Arguments
+    /// * `config_file` - Solana CLI config file; defaults to `~/.config/solana/cli/config.yml`,
+    ///   which is skipped when it does not exist.
+    /// * `url` - JSON RPC URL or moniker (`localhost`, `devnet`, `testnet`, `mainnet-beta`).
+    /// * `ws_url` - WebSocket URL; computed from the JSON RPC URL when unset.
+    /// * `commitment` - Commitment level (`processed`, `confirmed` or `finalized`).
+    pub fn resolve(
+        config_file: Option<&Path>,
+        url: Option<&str>,
+        ws_url: Option<&str>,
+        commitment: Option<&str>,
+    ) -> Result<Self> {
+        let config = match config_file {
+            Some(path) => load_config(path)?,
+            None => match CONFIG_FILE.as_deref().map(Path::new) {
+                Some(path) if path.exists() => load_config(path)?,
+                // Without a Solana CLI config, talk to a local validator
+                _ => Config {
+                    json_rpc_url: String::from(DEFAULT_RPC_URL),
+                    ..Config::default()
+                },
+            },
+        };
+
+        let (url, ws_url, commitment) = (
+            url.unwrap_or_default(),
+            ws_url.unwrap_or_default(),
+            commitment.unwrap_or_default(),
+        );
+        let (_, json_rpc_url) =
+            ConfigInput::compute_json_rpc_url_setting(url, &config.json_rpc_url);
+        // A WebSocket URL from the config file belongs to the config's RPC URL, not to --url
+        let config_ws_url = if url.is_empty() {
+            config.websocket_url.as_str()
+        } else {
+            ""
+        };
+        let (_, websocket_url) = ConfigInput::compute_websocket_url_setting(
+            ws_url,
+            config_ws_url,
+            url,
+            &config.json_rpc_url,
+        );
+        let (_, commitment) =
+            ConfigInput::compute_commitment_config(commitment, &config.commitment);
+
+        Ok(RpcConfig {
+            url: json_rpc_url,
+            ws_url: websocket_url,
+            commitment,
+        })
+    }
+
+    /// Creates an RPC client for the endpoint and commitment level.
+    pub fn rpc_client(&self) -> Arc<RpcClient> {
+        Arc::new(RpcClient::new_with_commitment(
+            self.url.clone(),
+            self.commitment,
+        ))
+    }
+}
+
+/// Loads a Solana CLI config file.
+fn load_config(path: &Path) -> Result<Config> {
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| anyhow!("Invalid config file path {}", path.display()))?;
+    Config::load(path_str).map_err(|e| anyhow!("Failed to read config {}: {e}", path.display()))
+}
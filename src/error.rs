@@ -0,0 +1,189 @@
+use num_traits::FromPrimitive;
+use solana_banks_client::BanksClientError;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    nonblocking::pubsub_client::PubsubClientError,
+    rpc_request::{RpcError, RpcResponseErrorData},
+};
+use solana_sdk::{
+    instruction::InstructionError, program_error::ProgramError, pubkey::Pubkey,
+    transaction::TransactionError,
+};
+use spl_token_client::{
+    client::ProgramClientError,
+    spl_token_2022::{self, error::TokenError},
+    token::TokenError as TokenClientError,
+};
+use thiserror::Error;
+
+/// Result type of the confidential token operations.
+pub type Result<T, E = ConfidentialError> = std::result::Result<T, E>;
+
+// =================== Error Type ===================
+
+/// Errors returned by the confidential token operations.
+///
+/// Token-2022 program errors, client-side proof generation errors and RPC failures are
+/// mapped to the variants below, so callers can react to the common confidential transfer
+/// failures without matching on error strings.
+#[derive(Error, Debug)]
+pub enum ConfidentialError {
+    #[error("Insufficient confidential available balance")]
+    InsufficientBalance, // Detected while generating the proofs of a transfer or withdraw
+    #[error("Insufficient public token balance")]
+    InsufficientPublicBalance, // E.g. a deposit or public transfer over the public balance
+    #[error("Maximum pending balance credit counter exceeded, apply the pending balance first")]
+    PendingCreditCounterExceeded,
+    #[error("Pending balance must be applied first")]
+    PendingBalanceNotApplied,
+    #[error("Token account is not approved for confidential transfers")]
+    AccountNotApproved,
+    #[error("Token account does not accept confidential credits")]
+    ConfidentialCreditsDisabled,
+    #[error("Token account does not accept non-confidential credits")]
+    NonConfidentialCreditsDisabled,
+    #[error("ElGamal keypair does not match the ElGamal pubkey of the token account")]
+    ElGamalPubkeyMismatch,
+    #[error("Auditor keypair does not match the auditor configured on the mint")]
+    AuditorMismatch,
+    #[error("Proof generation failed: {0}")]
+    ProofGeneration(String),
+    #[error("Decryption failed: {0}")]
+    Decryption(String),
+    #[error("RPC error: {0}")]
+    Rpc(String),
+    #[error("Token program error: {0}")]
+    Token(TokenError),
+    #[error("Program error: {0}")]
+    Program(ProgramError),
+    #[error("Token client error: {0}")]
+    TokenClient(TokenClientError),
+    #[error("{0}")]
+    Invalid(String), // Invalid input or account state detected before sending a transaction
+}
+
+// =================== Conversions ===================
+
+impl From<TokenError> for ConfidentialError {
+    fn from(error: TokenError) -> Self {
+        match error {
+            // Returned by the proof generation of a transfer or withdraw
+            TokenError::InsufficientFunds => Self::InsufficientBalance,
+            TokenError::MaximumPendingBalanceCreditCounterExceeded => {
+                Self::PendingCreditCounterExceeded
+            }
+            TokenError::ConfidentialTransferAccountNotApproved => Self::AccountNotApproved,
+            TokenError::ConfidentialTransferDepositsAndTransfersDisabled => {
+                Self::ConfidentialCreditsDisabled
+            }
+            TokenError::NonConfidentialTransfersDisabled => Self::NonConfidentialCreditsDisabled,
+            TokenError::ConfidentialTransferElGamalPubkeyMismatch => Self::ElGamalPubkeyMismatch,
+            TokenError::ProofGeneration => Self::ProofGeneration(error.to_string()),
+            TokenError::AccountDecryption => Self::Decryption(error.to_string()),
+            error => Self::Token(error),
+        }
+    }
+}
+
+impl From<ProgramError> for ConfidentialError {
+    fn from(error: ProgramError) -> Self {
+        // Custom program errors raised by Token-2022 carry a `TokenError` code
+        match error {
+            ProgramError::Custom(code) => match TokenError::from_u32(code) {
+                // On-chain, only public balances are checked this way
+                Some(TokenError::InsufficientFunds) => Self::InsufficientPublicBalance,
+                Some(token_error) => token_error.into(),
+                None => Self::Program(error),
+            },
+            error => Self::Program(error),
+        }
+    }
+}
+
+impl From<ClientError> for ConfidentialError {
+    fn from(error: ClientError) -> Self {
+        // Only custom errors of the Token-2022 program are token errors; the other programs
+        // in a transaction (e.g. the system program) reuse the same small error codes
+        if let Some(TransactionError::InstructionError(_, InstructionError::Custom(code))) =
+            error.get_transaction_error()
+            && failed_program(&error) == Some(spl_token_2022::id())
+        {
+            return ProgramError::Custom(code).into();
+        }
+
+        Self::Rpc(error.to_string())
+    }
+}
+
+impl From<TokenClientError> for ConfidentialError {
+    fn from(error: TokenClientError) -> Self {
+        match error {
+            TokenClientError::Client(error) => error.into(),
+            TokenClientError::Program(error) => error.into(),
+            TokenClientError::ProofGeneration => Self::ProofGeneration(error.to_string()),
+            TokenClientError::AccountDecryption => Self::Decryption(error.to_string()),
+            TokenClientError::NotEnoughFunds => Self::InsufficientBalance,
+            error => Self::TokenClient(error),
+        }
+    }
+}
+
+impl From<BanksClientError> for ConfidentialError {
+    fn from(error: BanksClientError) -> Self {
+        // Like an RPC preflight failure, only a simulation error carries the logs that tell
+        // which program failed
+        if let BanksClientError::SimulationError {
+            err: TransactionError::InstructionError(_, InstructionError::Custom(code)),
+            logs,
+            ..
+        } = &error
+            && failed_program_in_logs(logs) == Some(spl_token_2022::id())
+        {
+            return ProgramError::Custom(*code).into();
+        }
+
+        Self::Rpc(error.to_string())
+    }
+}
+
+impl From<ProgramClientError> for ConfidentialError {
+    fn from(error: ProgramClientError) -> Self {
+        // The error type depends on the program client: RPC node or in-process bank
+        let error = match error.downcast::<ClientError>() {
+            Ok(error) => return (*error).into(),
+            Err(error) => error,
+        };
+        match error.downcast::<BanksClientError>() {
+            Ok(error) => (*error).into(),
+            Err(error) => Self::Rpc(error.to_string()),
+        }
+    }
+}
+
+impl From<PubsubClientError> for ConfidentialError {
+    fn from(error: PubsubClientError) -> Self {
+        Self::Rpc(error.to_string())
+    }
+}
+
+/// Reads the program that failed a transaction from its preflight simulation logs
+/// (`Program <id> failed: ...`).
+fn failed_program(error: &ClientError) -> Option<Pubkey> {
+    let ClientErrorKind::RpcError(RpcError::RpcResponseError {
+        data: RpcResponseErrorData::SendTransactionPreflightFailure(simulation),
+        ..
+    }) = error.kind()
+    else {
+        return None;
+    };
+
+    failed_program_in_logs(simulation.logs.as_ref()?)
+}
+
+/// Reads the program that failed a transaction from its logs (`Program <id> failed: ...`).
+fn failed_program_in_logs(logs: &[String]) -> Option<Pubkey> {
+    logs.iter().rev().find_map(|log| {
+        let (program, _) = log.strip_prefix("Program ")?.split_once(" failed: ")?;
+        program.parse().ok()
+    })
+}
@@ -0,0 +1,144 @@
+use anyhow::{anyhow, bail, Ok, Result};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+    transaction::Transaction,
+};
+use solana_system_interface::instruction as system_instruction;
+use std::time::Duration;
+use tokio::time::{sleep, Instant};
+
+use crate::helper::format_token_amount;
+
+// =================== Constants ===================
+
+/// Time an airdrop may take, from the first request to its confirmation.
+pub const DEFAULT_AIRDROP_DEADLINE: Duration = Duration::from_secs(60);
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(8);
+const SOL_DECIMALS: u8 = 9;
+
+// =================== Structs ===================
+
+/// Where the lamports of a funded account come from.
+pub enum FundingSource {
+    Airdrop { deadline: Duration }, // Faucet of the cluster, e.g. a local or devnet validator
+    Funder(Box<Keypair>),           // Existing account that pays, for clusters without a faucet
+}
+
+// =================== Funding ===================
+
+/// Tops `recipient` up to `lamports`. Accounts that already hold `lamports` are skipped, so
+/// funding the same users twice costs nothing.
+///
+/// # Returns
+/// * `Option<Signature>` - The airdrop or transfer signature, or `None` when skipped.
+pub async fn fund_account(
+    rpc_client: &RpcClient,
+    recipient: &Pubkey,
+    lamports: u64,
+    source: &FundingSource,
+) -> Result<Option<Signature>> {
+    let balance = rpc_client.get_balance(recipient).await?;
+    if balance >= lamports {
+        println!(
+            "✓ {} already holds {} SOL, skipping",
+            recipient,
+            format_token_amount(balance, SOL_DECIMALS)
+        );
+        return Ok(None);
+    }
+
+    // Only the missing lamports are sent
+    let shortfall = lamports - balance;
+    let sig = match source {
+        FundingSource::Airdrop { deadline } => {
+            airdrop(rpc_client, recipient, shortfall, *deadline).await?
+        }
+        FundingSource::Funder(funder) => {
+            transfer_lamports(rpc_client, funder, recipient, shortfall).await?
+        }
+    };
+    println!(
+        "✓ Funded {} with {} SOL: {}",
+        recipient,
+        format_token_amount(shortfall, SOL_DECIMALS),
+        sig
+    );
+
+    Ok(Some(sig))
+}
+
+/// Requests an airdrop and waits for its confirmation. Failed requests (e.g. a rate limited
+/// faucet) and pending confirmations are retried with exponential backoff until `deadline`.
+pub async fn airdrop(
+    rpc_client: &RpcClient,
+    recipient: &Pubkey,
+    lamports: u64,
+    deadline: Duration,
+) -> Result<Signature> {
+    let expires_at = Instant::now() + deadline;
+    let mut backoff = INITIAL_BACKOFF;
+
+    // Step 1: Request the airdrop
+    let sig = loop {
+        match rpc_client.request_airdrop(recipient, lamports).await {
+            Result::Ok(sig) => break sig,
+            Err(e) if Instant::now() + backoff < expires_at => {
+                println!("Airdrop request failed ({e}), retrying in {backoff:?}...");
+                sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            Err(e) => bail!("Airdrop to {recipient} failed within {deadline:?}: {e}"),
+        }
+    };
+
+    // Step 2: Wait for the airdrop transaction to be confirmed
+    backoff = INITIAL_BACKOFF;
+    loop {
+        match rpc_client.get_signature_status(&sig).await? {
+            Some(Result::Ok(())) => return Ok(sig),
+            Some(Err(e)) => bail!("Airdrop {sig} to {recipient} failed: {e}"),
+            None if Instant::now() + backoff < expires_at => {
+                sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            None => bail!("Airdrop {sig} to {recipient} was not confirmed within {deadline:?}"),
+        }
+    }
+}
+
+/// Transfers lamports from `funder` to `recipient` and waits for the confirmation.
+pub async fn transfer_lamports(
+    rpc_client: &RpcClient,
+    funder: &Keypair,
+    recipient: &Pubkey,
+    lamports: u64,
+) -> Result<Signature> {
+    let funder_balance = rpc_client.get_balance(&funder.pubkey()).await?;
+    if funder_balance < lamports {
+        bail!(
+            "Funder {} holds {} SOL, {} SOL are needed",
+            funder.pubkey(),
+            format_token_amount(funder_balance, SOL_DECIMALS),
+            format_token_amount(lamports, SOL_DECIMALS)
+        );
+    }
+
+    let transfer_ix = system_instruction::transfer(&funder.pubkey(), recipient, lamports);
+    let blockhash = rpc_client.get_latest_blockhash().await?;
+    let transaction = Transaction::new_signed_with_payer(
+        &[transfer_ix],
+        Some(&funder.pubkey()),
+        &[funder],
+        blockhash,
+    );
+
+    rpc_client
+        .send_and_confirm_transaction(&transaction)
+        .await
+        .map_err(|e| anyhow!("Transfer from funder {} failed: {e}", funder.pubkey()))
+}
@@ -1,58 +1,480 @@
-use anyhow::{anyhow, Ok, Result};
-use solana_client::nonblocking::rpc_client::RpcClient;
+use anyhow::{anyhow, Result};
+use solana_banks_client::{BanksClient, BanksClientError};
+use solana_banks_interface::BanksTransactionResultWithSimulation;
+use solana_client::{
+    client_error::ClientError,
+    nonblocking::rpc_client::RpcClient,
+    rpc_custom_error::JSON_RPC_SERVER_ERROR_SEND_TRANSACTION_PREFLIGHT_FAILURE,
+    rpc_request::{RpcError, RpcResponseErrorData},
+};
 use solana_sdk::{
-    instruction::Instruction, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey, signature::Keypair,
-    signer::Signer, transaction::Transaction,
+    account::from_account,
+    clock::{Clock, Epoch},
+    compute_budget::ComputeBudgetInstruction,
+    instruction::Instruction,
+    message::Message,
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{read_keypair_file, Keypair},
+    signer::Signer,
+    sysvar,
+    transaction::{Transaction, TransactionError},
 };
+use solana_signer::EncodableKey;
+use solana_system_interface::instruction as system_instruction;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
 use spl_token_client::{
-    client::{ProgramRpcClientSendTransaction, RpcClientResponse},
+    client::{
+        ProgramBanksClientProcessTransaction, ProgramClientResult, ProgramRpcClientSendTransaction,
+        RpcClientResponse, SendTransaction, SendTransactionBanksClient, SendTransactionRpc,
+        SimulateTransaction, SimulateTransactionBanksClient, SimulateTransactionRpc,
+    },
     spl_token_2022::{
+        self, amount_to_ui_amount_string_trimmed,
         extension::{
-            confidential_transfer::ConfidentialTransferMint, BaseStateWithExtensions,
-            StateWithExtensionsOwned,
+            confidential_transfer::{
+                ConfidentialTransferAccount, ConfidentialTransferMint,
+                PENDING_BALANCE_LO_BIT_LENGTH,
+            },
+            BaseStateWithExtensions, StateWithExtensionsOwned,
+        },
+        solana_zk_sdk::{
+            encryption::{
+                auth_encryption::{AeCiphertext, AeKey},
+                elgamal::{ElGamalCiphertext, ElGamalKeypair, ElGamalPubkey},
+                pod::elgamal::PodElGamalPubkey,
+            },
+            zk_elgamal_proof_program::{
+                self,
+                instruction::{ContextStateInfo, ProofInstruction},
+                proof_data::{BatchedRangeProofContext, BatchedRangeProofU256Data},
+                state::ProofContextState,
+            },
         },
-        solana_zk_sdk::encryption::{auth_encryption::AeKey, elgamal::ElGamalKeypair},
         state::{Account, Mint},
     },
     token::Token,
 };
+use std::{future::Future, mem::size_of, path::Path, pin::Pin};
+
+use crate::{
+    error::ConfidentialError,
+    funding::{fund_account, FundingSource},
+};
 
 // =================== Structs ===================
 
-/// Holds the confidential token account keypair and associated cryptographic keys.
+/// Holds the confidential token account and associated cryptographic keys.
 pub struct ConfTokenAccountRes {
-    pub token_account_kp: Keypair,       // Token account keypair
-    pub user_elgamal_kp: ElGamalKeypair, // ElGamal keypair for confidential encryption
-    pub user_aes_kp: AeKey,              // AE key for confidential encryption
+    pub token_account: Pubkey,             // Token account address
+    pub token_account_kp: Option<Keypair>, // Token account keypair (None at the associated token address)
+    pub user_elgamal_kp: ElGamalKeypair,   // ElGamal keypair for confidential encryption
+    pub user_aes_kp: AeKey,                // AE key for confidential encryption
 }
 
-// =================== Helper Functions ===================
+/// Decrypted balances of a confidential token account (in base units).
+pub struct ConfidentialBalances {
+    pub pending: u64,   // Pending balance (decrypted with the ElGamal secret key)
+    pub available: u64, // Available balance (decrypted with the AE key)
+}
 
-/// Generates a new keypair and funds it with 1 SOL from the faucet.
-pub async fn keypair_gen(client: &RpcClient) -> Result<Keypair> {
-    let keypair = Keypair::new();
+// =================== Program Clients ===================
 
-    let sig = client
-        .request_airdrop(&keypair.pubkey(), LAMPORTS_PER_SOL)
-        .await?;
+/// Output of a `Token` client transaction that can be reported to the user.
+pub trait TokenResponse {
+    fn report(&self, content: &str);
+}
+
+impl TokenResponse for RpcClientResponse {
+    fn report(&self, content: &str) {
+        match self {
+            RpcClientResponse::Simulation(rpc_res) => report_simulation(
+                content,
+                rpc_res.logs.as_deref().unwrap_or_default(),
+                rpc_res.units_consumed,
+                rpc_res.err.as_ref(),
+            ),
+            RpcClientResponse::Signature(sig) => {
+                println!("Sig for {} is: {}", content, sig);
+            }
+            _ => {}
+        };
+    }
+}
+
+/// A transaction simulated in an in-process bank, see [`ProgramBanksClientSimulate`].
+impl TokenResponse for BanksTransactionResultWithSimulation {
+    fn report(&self, content: &str) {
+        let details = self.simulation_details.as_ref();
+        report_simulation(
+            content,
+            details
+                .map(|details| details.logs.as_slice())
+                .unwrap_or_default(),
+            details.map(|details| details.units_consumed),
+            self.result
+                .as_ref()
+                .and_then(|result| result.as_ref().err()),
+        );
+    }
+}
+
+/// Prints the program logs, the compute units of each instruction and the error of a
+/// simulated transaction.
+fn report_simulation(
+    content: &str,
+    logs: &[String],
+    units_consumed: Option<u64>,
+    err: Option<&TransactionError>,
+) {
+    println!("🧪 Simulated {}", content);
+    for log in logs {
+        println!("The Log: {}", log);
+    }
 
-    client.confirm_transaction(&sig).await?;
+    // Every instruction logs "invoke [depth]"; metered programs also log the units they
+    // consumed before their "success" or "failed" line. Only top-level instructions are listed.
+    let (mut instruction, mut depth) = (0, 0);
+    for log in logs.iter().filter_map(|log| log.strip_prefix("Program ")) {
+        if let Some((_, invoke)) = log.split_once(" invoke [") {
+            depth = invoke.trim_end_matches(']').parse().unwrap_or(depth + 1);
+            if depth == 1 {
+                instruction += 1;
+            }
+        } else if let Some((program, units)) = log.split_once(" consumed ") {
+            if depth == 1 {
+                println!("- Instruction {} ({}): {}", instruction, program, units);
+            }
+        } else if log.ends_with(" success") || log.contains(" failed: ") {
+            depth -= 1;
+        }
+    }
+    match units_consumed {
+        Some(units) => println!("- Total: {} compute units", units),
+        None => println!("- Total: unknown compute units"),
+    }
+    if let Some(err) = err {
+        println!("❌ Simulation of {} failed: {}", content, err);
+    }
+}
+
+/// `ProgramBanksClientProcessTransaction` processes transactions in-process and returns nothing.
+impl TokenResponse for () {
+    fn report(&self, content: &str) {
+        println!("Processed {}", content);
+    }
+}
+
+/// A `Token` client transaction sender the confidential operations can run on, e.g.
+/// `ProgramRpcClientSendOrSimulate` (RPC node) or `ProgramBanksClientProcessWithPreflight`
+/// (in-process bank from `solana-program-test`).
+pub trait ConfidentialSender: SendTransaction<Output: TokenResponse> + SimulateTransaction {}
+
+impl<T> ConfidentialSender for T where
+    T: SendTransaction<Output: TokenResponse> + SimulateTransaction
+{
+}
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Sends transactions through an RPC node like `ProgramRpcClientSendTransaction`, or with
+/// `dry_run` only simulates them, so nothing is committed and the response carries the
+/// simulation logs and compute units. A failed simulation is reported, then returned as an
+/// error like a failed preflight.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProgramRpcClientSendOrSimulate {
+    pub dry_run: bool,
+}
+
+impl SendTransaction for ProgramRpcClientSendOrSimulate {
+    type Output = RpcClientResponse;
+}
 
-    // Wait for confirmation
-    loop {
-        let is_confirmed = client.confirm_transaction(&sig).await?;
-        if is_confirmed {
-            break;
+impl SendTransactionRpc for ProgramRpcClientSendOrSimulate {
+    fn send<'a>(
+        &self,
+        client: &'a RpcClient,
+        transaction: &'a Transaction,
+    ) -> BoxFuture<'a, ProgramClientResult<Self::Output>> {
+        if !self.dry_run {
+            return ProgramRpcClientSendTransaction.send(client, transaction);
         }
+
+        Box::pin(async move {
+            let response = ProgramRpcClientSendTransaction
+                .simulate(client, transaction)
+                .await?;
+            let RpcClientResponse::Simulation(simulation) = &response else {
+                return Ok(response);
+            };
+            let Some(err) = &simulation.err else {
+                return Ok(response);
+            };
+
+            // A failed simulation fails like a failed preflight when sending, so the operation
+            // stops at the failed step and the error maps the same way
+            response.report("transaction");
+            Err(ClientError::from(RpcError::RpcResponseError {
+                code: JSON_RPC_SERVER_ERROR_SEND_TRANSACTION_PREFLIGHT_FAILURE,
+                message: format!("Transaction simulation failed: {err}"),
+                data: RpcResponseErrorData::SendTransactionPreflightFailure(simulation.clone()),
+            })
+            .into())
+        })
+    }
+}
+
+impl SimulateTransaction for ProgramRpcClientSendOrSimulate {
+    type SimulationOutput = RpcClientResponse;
+}
+
+impl SimulateTransactionRpc for ProgramRpcClientSendOrSimulate {
+    fn simulate<'a>(
+        &self,
+        client: &'a RpcClient,
+        transaction: &'a Transaction,
+    ) -> BoxFuture<'a, ProgramClientResult<Self::SimulationOutput>> {
+        ProgramRpcClientSendTransaction.simulate(client, transaction)
+    }
+}
+
+/// Processes transactions in an in-process bank like `ProgramBanksClientProcessTransaction`,
+/// but simulates them first. A failed transaction then returns its simulation logs, which
+/// tell which program failed, so Token-2022 errors map to typed [`ConfidentialError`]s.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProgramBanksClientProcessWithPreflight;
+
+impl SendTransaction for ProgramBanksClientProcessWithPreflight {
+    type Output = ();
+}
+
+impl SendTransactionBanksClient for ProgramBanksClientProcessWithPreflight {
+    fn send<'a>(
+        &self,
+        client: &'a mut BanksClient,
+        transaction: Transaction,
+    ) -> BoxFuture<'a, ProgramClientResult<Self::Output>> {
+        Box::pin(async move {
+            client
+                .process_transaction_with_preflight(transaction)
+                .await
+                .map_err(Into::into)
+        })
+    }
+}
+
+impl SimulateTransaction for ProgramBanksClientProcessWithPreflight {
+    type SimulationOutput = BanksTransactionResultWithSimulation;
+}
+
+impl SimulateTransactionBanksClient for ProgramBanksClientProcessWithPreflight {
+    fn simulate<'a>(
+        &self,
+        client: &'a mut BanksClient,
+        transaction: Transaction,
+    ) -> BoxFuture<'a, ProgramClientResult<Self::SimulationOutput>> {
+        ProgramBanksClientProcessTransaction.simulate(client, transaction)
+    }
+}
+
+/// Simulates transactions in an in-process bank and reports them like a dry run on an RPC
+/// node, so nothing is committed. A failed simulation fails like
+/// `ProgramBanksClientProcessWithPreflight`, after its logs are reported.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProgramBanksClientSimulate;
+
+impl SendTransaction for ProgramBanksClientSimulate {
+    type Output = BanksTransactionResultWithSimulation;
+}
+
+impl SendTransactionBanksClient for ProgramBanksClientSimulate {
+    fn send<'a>(
+        &self,
+        client: &'a mut BanksClient,
+        transaction: Transaction,
+    ) -> BoxFuture<'a, ProgramClientResult<Self::Output>> {
+        Box::pin(async move {
+            let simulation = client.simulate_transaction(transaction).await?;
+            let Some(Err(err)) = simulation.result.clone() else {
+                return Ok(simulation);
+            };
+
+            simulation.report("transaction");
+            let (logs, units_consumed, return_data) = match simulation.simulation_details {
+                Some(details) => (details.logs, details.units_consumed, details.return_data),
+                None => (Vec::new(), 0, None),
+            };
+            Err(BanksClientError::SimulationError {
+                err,
+                logs,
+                units_consumed,
+                return_data,
+            }
+            .into())
+        })
+    }
+}
+
+impl SimulateTransaction for ProgramBanksClientSimulate {
+    type SimulationOutput = BanksTransactionResultWithSimulation;
+}
+
+impl SimulateTransactionBanksClient for ProgramBanksClientSimulate {
+    fn simulate<'a>(
+        &self,
+        client: &'a mut BanksClient,
+        transaction: Transaction,
+    ) -> BoxFuture<'a, ProgramClientResult<Self::SimulationOutput>> {
+        ProgramBanksClientProcessTransaction.simulate(client, transaction)
+    }
+}
+
+// =================== Helper Functions ===================
+
+/// Reads a keypair from a JSON keypair file (as written by `solana-keygen`).
+pub fn read_keypair(path: &Path) -> Result<Keypair> {
+    read_keypair_file(path).map_err(|e| anyhow!("Failed to read keypair {}: {e}", path.display()))
+}
+
+/// Parses a decimal token amount such as "12.345" into base units for a mint with `decimals`.
+/// Fails instead of overflowing when the amount does not fit into a `u64`.
+pub fn parse_token_amount(amount: &str, decimals: u8) -> Result<u64> {
+    let amount = amount.trim();
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+
+    let is_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+    if (whole.is_empty() && fraction.is_empty()) || !is_digits(whole) || !is_digits(fraction) {
+        return Err(anyhow!("Invalid token amount: {amount:?}"));
     }
+    if fraction.len() > decimals as usize {
+        return Err(anyhow!(
+            "Token amount {amount} has more than {decimals} decimal places"
+        ));
+    }
+
+    let too_large =
+        || anyhow!("Token amount {amount} is too large for a mint with {decimals} decimals");
+
+    // Whole part scaled to base units
+    let scale = 10u64.checked_pow(decimals as u32).ok_or_else(too_large)?;
+    let whole = match whole {
+        "" => 0,
+        whole => whole.parse::<u64>().map_err(|_| too_large())?,
+    };
+    let whole = whole.checked_mul(scale).ok_or_else(too_large)?;
+
+    // Fractional part right-padded with zeros to `decimals` digits
+    let fraction = match fraction {
+        "" => 0,
+        fraction => {
+            let padding = 10u64.pow((decimals as usize - fraction.len()) as u32);
+            fraction.parse::<u64>().map_err(|_| too_large())? * padding
+        }
+    };
+
+    whole.checked_add(fraction).ok_or_else(too_large)
+}
+
+/// Formats an amount in base units as a decimal token amount, e.g. `12345000` with 6 decimals as "12.345".
+pub fn format_token_amount(amount: u64, decimals: u8) -> String {
+    amount_to_ui_amount_string_trimmed(amount, decimals)
+}
+
+/// Reads the number of decimals from a mint account.
+pub async fn fetch_mint_decimals(
+    rpc_client: &RpcClient,
+    mint: &Pubkey,
+) -> Result<u8, ConfidentialError> {
+    let mint_data = rpc_client.get_account_data(mint).await?;
+    let mint_state = StateWithExtensionsOwned::<Mint>::unpack(mint_data).map_err(|e| {
+        ConfidentialError::Invalid(format!("Account {mint} is not a Token-2022 mint: {e}"))
+    })?;
+
+    Ok(mint_state.base.decimals)
+}
+
+/// Reads the mint of a Token-2022 token account.
+pub async fn fetch_token_account_mint(
+    rpc_client: &RpcClient,
+    token_account: &Pubkey,
+) -> Result<Pubkey, ConfidentialError> {
+    let account_data = rpc_client.get_account_data(token_account).await?;
+    let account_state = StateWithExtensionsOwned::<Account>::unpack(account_data).map_err(|e| {
+        ConfidentialError::Invalid(format!(
+            "Account {token_account} is not a Token-2022 token account: {e}"
+        ))
+    })?;
+
+    Ok(account_state.base.mint)
+}
+
+/// Reads an ElGamal keypair from a JSON file.
+pub fn read_elgamal_keypair(path: &Path) -> Result<ElGamalKeypair> {
+    ElGamalKeypair::read_from_file(path)
+        .map_err(|e| anyhow!("Failed to read ElGamal keypair {}: {e}", path.display()))
+}
+
+/// Derives the ElGamal keypair and AE key of a confidential token account.
+/// Both keys are derived from the owner's signature over the token account address,
+/// so they can be recreated at any time from the owner keypair.
+pub fn derive_confidential_keys(
+    owner: &Keypair,
+    token_account: &Pubkey,
+) -> Result<(ElGamalKeypair, AeKey), ConfidentialError> {
+    let elgamal_kp =
+        ElGamalKeypair::new_from_signer(owner, &token_account.to_bytes()).map_err(|e| {
+            ConfidentialError::Invalid(format!("Unable to create ElGamal keypair: {e}"))
+        })?;
+    let aes_kp = AeKey::new_from_signer(owner, &token_account.to_bytes())
+        .map_err(|e| ConfidentialError::Invalid(format!("Unable to create AE key: {e}")))?;
+
+    Ok((elgamal_kp, aes_kp))
+}
+
+/// Derives the ElGamal keypair that confidential transfer fees of a mint are encrypted under.
+/// It is derived from the withdraw withheld authority's signature over the mint address and
+/// a fixed suffix, so it differs from the keys derived for the mint itself.
+pub fn derive_withdraw_withheld_elgamal_keypair(
+    authority: &Keypair,
+    mint: &Pubkey,
+) -> Result<ElGamalKeypair, ConfidentialError> {
+    let seed = [mint.as_ref(), b"withdraw-withheld"].concat();
+    ElGamalKeypair::new_from_signer(authority, &seed).map_err(|e| {
+        ConfidentialError::Invalid(format!(
+            "Unable to create withdraw withheld ElGamal keypair: {e}"
+        ))
+    })
+}
+
+/// Returns the associated token address of `owner` for a Token-2022 `mint`.
+pub fn associated_token_address(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(owner, mint, &spl_token_2022::ID)
+}
+
+/// Reads the current epoch from the clock sysvar (transfer fees are configured per epoch).
+pub async fn fetch_current_epoch<T: ConfidentialSender>(
+    token: &Token<T>,
+) -> Result<Epoch, ConfidentialError> {
+    let account = token.get_account(sysvar::clock::id()).await?;
+    let clock: Clock = from_account(&account)
+        .ok_or_else(|| ConfidentialError::Invalid(String::from("Malformed clock sysvar")))?;
+
+    Ok(clock.epoch)
+}
+
+/// Generates a new keypair and funds it with 1 SOL, from the faucet or a funder keypair.
+pub async fn keypair_gen(client: &RpcClient, source: &FundingSource) -> Result<Keypair> {
+    let keypair = Keypair::new();
+    fund_account(client, &keypair.pubkey(), LAMPORTS_PER_SOL, source).await?;
 
     Ok(keypair)
 }
 
 /// Fetches and prints the ConfidentialTransferMint extension for a mint account.
-pub async fn fetch_mint_account(
+pub async fn fetch_mint_account<T: ConfidentialSender>(
     pub_key: &Pubkey,
-    rpc_client: &Token<ProgramRpcClientSendTransaction>,
+    rpc_client: &Token<T>,
 ) -> Result<()> {
     let account = rpc_client.get_account(*pub_key).await?;
 
@@ -60,17 +482,71 @@ pub async fn fetch_mint_account(
 
     let confirm = state.get_extension::<ConfidentialTransferMint>()?;
     println!("is auto approve:  {:?}", confirm.auto_approve_new_accounts);
+    println!("auditor:  {:?}", confirm.auditor_elgamal_pubkey);
 
     Ok(())
 }
 
+/// Reads the ElGamal pubkey of a recipient from its `ConfidentialTransferAccount` extension.
+/// Fails early if the account belongs to another mint, is not approved, or refuses
+/// confidential credits, since the transfer would be rejected on-chain anyway.
+pub async fn fetch_recipient_elgamal_pubkey<T: ConfidentialSender>(
+    token: &Token<T>,
+    recipient_token_account: &Pubkey,
+) -> Result<ElGamalPubkey, ConfidentialError> {
+    let account = token.get_account_info(recipient_token_account).await?;
+    if account.base.mint != *token.get_address() {
+        return Err(ConfidentialError::Invalid(format!(
+            "Recipient {recipient_token_account} is a token account of mint {}",
+            account.base.mint
+        )));
+    }
+
+    let extension = account
+        .get_extension::<ConfidentialTransferAccount>()
+        .map_err(|_| {
+            ConfidentialError::Invalid(format!(
+                "Recipient {recipient_token_account} is not a confidential token account"
+            ))
+        })?;
+    if !bool::from(extension.approved) {
+        return Err(ConfidentialError::AccountNotApproved);
+    }
+    if !bool::from(extension.allow_confidential_credits) {
+        return Err(ConfidentialError::ConfidentialCreditsDisabled);
+    }
+
+    ElGamalPubkey::try_from(extension.elgamal_pubkey).map_err(|e| {
+        ConfidentialError::Invalid(format!(
+            "Invalid ElGamal pubkey on {recipient_token_account}: {e}"
+        ))
+    })
+}
+
+/// Reads the auditor ElGamal pubkey from the mint's ConfidentialTransferMint extension.
+/// Returns `None` when the mint has no auditor configured.
+pub async fn fetch_auditor_elgamal_pubkey<T: ConfidentialSender>(
+    token: &Token<T>,
+) -> Result<Option<ElGamalPubkey>, ConfidentialError> {
+    let mint = token.get_mint_info().await?;
+    let extension = mint.get_extension::<ConfidentialTransferMint>()?;
+
+    Option::<PodElGamalPubkey>::from(extension.auditor_elgamal_pubkey)
+        .map(|auditor| {
+            ElGamalPubkey::try_from(auditor).map_err(|e| {
+                ConfidentialError::Invalid(format!("Invalid auditor ElGamal pubkey on mint: {e}"))
+            })
+        })
+        .transpose()
+}
+
 /// Submits a vector of instructions as a transaction and waits for confirmation.
 pub async fn complete_ixs(
     rpc_client: &RpcClient,
     ix: Vec<Instruction>,
     signers: &[&Keypair],
     payer: &Keypair,
-) -> Result<()> {
+) -> Result<(), ConfidentialError> {
     let recent_blockhash = rpc_client.get_latest_blockhash().await?;
 
     let trx =
@@ -84,28 +560,114 @@ pub async fn complete_ixs(
 }
 
 /// Handles and prints the response from a token client transaction.
-pub async fn handle_token_response(sig: &RpcClientResponse, content: String) -> Result<()> {
-    match sig {
-        RpcClientResponse::Simulation(rpc_res) => {
-            if let Some(logs) = rpc_res.logs.clone() {
-                for log in logs {
-                    println!("The Log: {}", log);
-                }
-            }
-        }
-        RpcClientResponse::Signature(sig) => {
-            println!("Sig for {} is: {}", content, sig.to_string());
-        }
-        _ => {}
+pub async fn handle_token_response(
+    sig: &impl TokenResponse,
+    content: String,
+) -> Result<(), ConfidentialError> {
+    sig.report(&content);
+
+    Ok(())
+}
+
+/// Creates a context state account holding a verified 256-bit batched range proof.
+///
+/// The proof is too large to be verified in the account creation transaction, and its
+/// verification exceeds the default compute budget, so it is verified in a second
+/// transaction sent through `token`, which requests the compute units it needs.
+pub async fn create_range_proof_u256_context_state_account<T: ConfidentialSender>(
+    token: &Token<T>,
+    context_state_kp: &Keypair,
+    authority: &Keypair,
+    proof_data: &BatchedRangeProofU256Data,
+) -> Result<(), ConfidentialError> {
+    let space = size_of::<ProofContextState<BatchedRangeProofContext>>();
+    let rent: Rent = from_account(&token.get_account(sysvar::rent::id()).await?)
+        .ok_or_else(|| ConfidentialError::Invalid(String::from("Malformed rent sysvar")))?;
+
+    // The authority funds the context state account and gets the rent back when closing it
+    let create_account_ix = system_instruction::create_account(
+        &authority.pubkey(),
+        &context_state_kp.pubkey(),
+        rent.minimum_balance(space),
+        space as u64,
+        &zk_elgamal_proof_program::id(),
+    );
+    token
+        .process_ixs(&[create_account_ix], &[authority, context_state_kp])
+        .await?;
+
+    let context_state_info = ContextStateInfo {
+        context_state_account: &context_state_kp.pubkey(),
+        context_state_authority: &authority.pubkey(),
     };
+    let verify_proof_ix = ProofInstruction::VerifyBatchedRangeProofU256
+        .encode_verify_proof(Some(context_state_info), proof_data);
+    token
+        .process_ixs::<[&Keypair; 0]>(&[verify_proof_ix], &[])
+        .await?;
 
     Ok(())
 }
 
+/// Serialized size in bytes of a transaction made of `instructions` and the compute unit
+/// limit instruction the `Token` client appends, with `fee_payer` paying the fees.
+/// Transactions larger than `PACKET_DATA_SIZE` (1232 bytes) are rejected by the cluster.
+pub fn transaction_size(instructions: &[Instruction], fee_payer: &Pubkey) -> usize {
+    let mut instructions = instructions.to_vec();
+    instructions.push(ComputeBudgetInstruction::set_compute_unit_limit(u32::MAX));
+    let message = Message::new(&instructions, Some(fee_payer));
+
+    // Compact array of 64 byte signatures (one length byte below 128 signatures), then the message
+    1 + 64 * message.header.num_required_signatures as usize + message.serialize().len()
+}
+
+/// Decrypts the pending and available balances of a confidential token account.
+///
+/// The pending balance is split into a low (16 bit) and a high (48 bit) part, both
+/// encrypted under the account's ElGamal pubkey. The available balance is read from
+/// `decryptable_available_balance`, which is encrypted with the owner's AE key.
+pub fn decrypt_confidential_balances(
+    extension: &ConfidentialTransferAccount,
+    elgamal_kp: &ElGamalKeypair,
+    aes_kp: &AeKey,
+) -> Result<ConfidentialBalances, ConfidentialError> {
+    // Decrypting with the wrong ElGamal key would brute force a discrete log and fail
+    if extension.elgamal_pubkey != PodElGamalPubkey::from(*elgamal_kp.pubkey()) {
+        return Err(ConfidentialError::ElGamalPubkeyMismatch);
+    }
+
+    let decryption_error = ConfidentialError::Decryption;
+    let decrypt_pending = |ciphertext| -> Result<u64, ConfidentialError> {
+        let ciphertext = ElGamalCiphertext::try_from(ciphertext)
+            .map_err(|e| decryption_error(format!("Malformed pending balance ciphertext: {e}")))?;
+        elgamal_kp
+            .secret()
+            .decrypt_u32(&ciphertext)
+            .ok_or_else(|| decryption_error(String::from("Failed to decrypt pending balance")))
+    };
+    let pending_lo = decrypt_pending(extension.pending_balance_lo)?;
+    let pending_hi = decrypt_pending(extension.pending_balance_hi)?;
+    let pending = pending_hi
+        .checked_shl(PENDING_BALANCE_LO_BIT_LENGTH)
+        .and_then(|hi| hi.checked_add(pending_lo))
+        .ok_or_else(|| decryption_error(String::from("Pending balance overflow")))?;
+
+    let available = AeCiphertext::try_from(extension.decryptable_available_balance)
+        .map_err(|e| decryption_error(format!("Malformed available balance ciphertext: {e}")))?;
+    let available = aes_kp
+        .decrypt(&available)
+        .ok_or_else(|| decryption_error(String::from("Failed to decrypt available balance")))?;
+
+    Ok(ConfidentialBalances { pending, available })
+}
+
 /// Fetches and prints the confidential token account and its extensions.
+/// When the account's ElGamal keypair and AE key are given, the pending and
+/// available confidential balances are decrypted and printed as well.
 pub async fn fetch_token_account_with_extensions(
     rpc_client: &RpcClient,
     token_account_pubkey: &Pubkey,
+    keys: Option<(&ElGamalKeypair, &AeKey)>,
 ) -> Result<()> {
     // Fetch raw account data from the chain &[u8] type data
     let account_data = rpc_client
@@ -122,9 +684,56 @@ pub async fn fetch_token_account_with_extensions(
     println!("\n Base Account: {:#?}", state_with_ext.base);
 
     // Find and print the ConfidentialTransfer extension if present
-    // let ext = state_with_ext.get_extension::<ConfidentialTransferAccount>()?;
+    let Some(ext) = state_with_ext
+        .get_extension::<ConfidentialTransferAccount>()
+        .ok()
+    else {
+        println!("\n No ConfidentialTransferAccount extension on this account");
+        return Ok(());
+    };
 
-    // println!("\n Confidential Token Account {:#?}", ext);
+    println!("\n Confidential Token Account:");
+    println!("- Approved: {}", bool::from(ext.approved));
+    println!("- ElGamal Pubkey: {}", ext.elgamal_pubkey);
+    println!(
+        "- Confidential credits allowed: {}",
+        bool::from(ext.allow_confidential_credits)
+    );
+    println!(
+        "- Non-confidential credits allowed: {}",
+        bool::from(ext.allow_non_confidential_credits)
+    );
+    println!(
+        "- Pending balance credit counter: {} (maximum {})",
+        u64::from(ext.pending_balance_credit_counter),
+        u64::from(ext.maximum_pending_balance_credit_counter)
+    );
+    println!(
+        "- Expected / actual pending balance credit counter: {} / {}",
+        u64::from(ext.expected_pending_balance_credit_counter),
+        u64::from(ext.actual_pending_balance_credit_counter)
+    );
+
+    match keys {
+        Some((elgamal_kp, aes_kp)) => {
+            let balances = decrypt_confidential_balances(ext, elgamal_kp, aes_kp)?;
+            let decimals = fetch_mint_decimals(rpc_client, &state_with_ext.base.mint).await?;
+            println!(
+                "- Pending balance: {} ({} base units)",
+                format_token_amount(balances.pending, decimals),
+                balances.pending
+            );
+            println!(
+                "- Available balance: {} ({} base units)",
+                format_token_amount(balances.available, decimals),
+                balances.available
+            );
+        }
+        None => {
+            println!("- Pending balance: <encrypted> (pass the owner keypair to decrypt)");
+            println!("- Available balance: <encrypted> (pass the owner keypair to decrypt)");
+        }
+    }
 
     Ok(())
 }
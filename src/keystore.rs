@@ -0,0 +1,169 @@
+use aes_gcm_siv::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    Aes256GcmSiv, Nonce,
+};
+use anyhow::{anyhow, bail, Ok, Result};
+use hmac::Hmac;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use solana_sdk::signature::Keypair;
+use spl_token_client::spl_token_2022::solana_zk_sdk::encryption::{
+    auth_encryption::AeKey,
+    elgamal::{ElGamalKeypair, ElGamalSecretKey},
+};
+use std::{env, fs, path::Path};
+
+// =================== Constants ===================
+
+/// Environment variable holding the keystore passphrase, for headless services.
+pub const PASSPHRASE_ENV: &str = "CONFIDENTIAL_SOLANA_PASSPHRASE";
+
+const VERSION: u8 = 1;
+const KDF: &str = "pbkdf2-hmac-sha256";
+const CIPHER: &str = "aes-256-gcm-siv";
+const KDF_ITERATIONS: u32 = 600_000;
+const MAX_KDF_ITERATIONS: u32 = 10_000_000; // Bounds the work a tampered keystore can demand
+const SALT_LEN: usize = 16;
+
+// Layout of the plaintext: owner keypair || ElGamal secret key || AE key
+const OWNER_LEN: usize = 64;
+const ELGAMAL_SECRET_LEN: usize = 32;
+const AE_KEY_LEN: usize = 16;
+
+// =================== Structs ===================
+
+/// Secrets of a confidential token account that are protected by a keystore.
+pub struct KeystoreSecrets {
+    pub owner: Keypair,             // Owner (authority) of the token account
+    pub elgamal_kp: ElGamalKeypair, // ElGamal keypair of the token account
+    pub aes_kp: AeKey,              // AE key of the token account
+}
+
+/// Contents of `keystore.json`: the secrets encrypted with a passphrase-derived key.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Keystore {
+    pub version: u8,
+    pub kdf: String,         // Key derivation function applied to the passphrase
+    pub iterations: u32,     // Iterations of the key derivation function
+    pub salt: Vec<u8>,       // Random salt of the key derivation function
+    pub cipher: String,      // Authenticated cipher encrypting the secrets
+    pub nonce: Vec<u8>,      // Random nonce of the cipher
+    pub ciphertext: Vec<u8>, // Encrypted secrets, including the authentication tag
+}
+
+// =================== Keystore ===================
+
+impl Keystore {
+    /// Encrypts the secrets with a key derived from `passphrase` and a fresh random salt.
+    pub fn encrypt(secrets: &KeystoreSecrets, passphrase: &str) -> Result<Self> {
+        let mut salt = vec![0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let cipher = keystore_cipher(passphrase, &salt, KDF_ITERATIONS)?;
+        let nonce = Aes256GcmSiv::generate_nonce(&mut OsRng);
+
+        let mut plaintext = Vec::with_capacity(OWNER_LEN + ELGAMAL_SECRET_LEN + AE_KEY_LEN);
+        plaintext.extend_from_slice(&secrets.owner.to_bytes());
+        plaintext.extend_from_slice(secrets.elgamal_kp.secret().as_bytes());
+        plaintext.extend_from_slice(&<[u8; AE_KEY_LEN]>::from(secrets.aes_kp.clone()));
+
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|_| anyhow!("Failed to encrypt keystore"))?;
+
+        Ok(Keystore {
+            version: VERSION,
+            kdf: String::from(KDF),
+            iterations: KDF_ITERATIONS,
+            salt,
+            cipher: String::from(CIPHER),
+            nonce: nonce.to_vec(),
+            ciphertext,
+        })
+    }
+
+    /// Decrypts the secrets. Fails on a wrong passphrase or a tampered keystore, including one
+    /// whose KDF iterations are too weak or too costly to derive.
+    pub fn decrypt(&self, passphrase: &str) -> Result<KeystoreSecrets> {
+        if self.version != VERSION || self.kdf != KDF || self.cipher != CIPHER {
+            bail!(
+                "Unsupported keystore (version {}, {}, {})",
+                self.version,
+                self.kdf,
+                self.cipher
+            );
+        }
+        if self.nonce.len() != 12 {
+            bail!("Malformed keystore nonce");
+        }
+        if !(KDF_ITERATIONS..=MAX_KDF_ITERATIONS).contains(&self.iterations) {
+            bail!(
+                "Unsupported keystore KDF iterations {} (expected {KDF_ITERATIONS} to {MAX_KDF_ITERATIONS})",
+                self.iterations
+            );
+        }
+
+        let cipher = keystore_cipher(passphrase, &self.salt, self.iterations)?;
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(&self.nonce), self.ciphertext.as_slice())
+            .map_err(|_| anyhow!("Wrong passphrase or corrupted keystore"))?;
+        if plaintext.len() != OWNER_LEN + ELGAMAL_SECRET_LEN + AE_KEY_LEN {
+            bail!("Malformed keystore secrets");
+        }
+
+        let (owner, rest) = plaintext.split_at(OWNER_LEN);
+        let (elgamal_secret, ae_key) = rest.split_at(ELGAMAL_SECRET_LEN);
+        let owner = Keypair::try_from(owner)
+            .map_err(|e| anyhow!("Malformed owner keypair in keystore: {e}"))?;
+        let elgamal_secret = ElGamalSecretKey::try_from(elgamal_secret)
+            .map_err(|e| anyhow!("Malformed ElGamal secret key in keystore: {e}"))?;
+        let aes_kp =
+            AeKey::try_from(ae_key).map_err(|e| anyhow!("Malformed AE key in keystore: {e}"))?;
+
+        Ok(KeystoreSecrets {
+            owner,
+            elgamal_kp: ElGamalKeypair::new(elgamal_secret),
+            aes_kp,
+        })
+    }
+
+    /// Reads a keystore from a JSON file.
+    pub fn read(path: &Path) -> Result<Self> {
+        let data = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read {}: {e}", path.display()))?;
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    /// Writes the keystore to a JSON file.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .map_err(|e| anyhow!("Failed to write {}: {e}", path.display()))?;
+        Ok(())
+    }
+}
+
+/// Reads the keystore passphrase from `CONFIDENTIAL_SOLANA_PASSPHRASE`, or prompts for it
+/// on the terminal (twice with `confirm`, e.g. when choosing a new passphrase).
+pub fn read_passphrase(prompt: &str, confirm: bool) -> Result<String> {
+    let passphrase = match env::var(PASSPHRASE_ENV).ok() {
+        Some(passphrase) => passphrase,
+        None => {
+            let passphrase = rpassword::prompt_password(prompt)?;
+            if confirm && rpassword::prompt_password("Confirm passphrase: ")? != passphrase {
+                bail!("Passphrases do not match");
+            }
+            passphrase
+        }
+    };
+    if passphrase.is_empty() {
+        bail!("Passphrase must not be empty");
+    }
+
+    Ok(passphrase)
+}
+
+/// Derives the cipher key from the passphrase with PBKDF2-HMAC-SHA256.
+fn keystore_cipher(passphrase: &str, salt: &[u8], iterations: u32) -> Result<Aes256GcmSiv> {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(passphrase.as_bytes(), salt, iterations, &mut key);
+    Aes256GcmSiv::new_from_slice(&key).map_err(|e| anyhow!("Invalid keystore key: {e}"))
+}
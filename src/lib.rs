@@ -0,0 +1,24 @@
+//! Confidential token operations on SPL Token 2022.
+//!
+//! [`ConfidentialClient`] wraps mint creation, account creation, deposits, transfers,
+//! withdrawals and balance queries for a single mint, and reports failures as
+//! [`ConfidentialError`]. The lower level building blocks are available in
+//! [`confidential`] and [`helper`].
+
+pub mod client;
+pub use client::*;
+
+pub mod error;
+pub use error::ConfidentialError;
+
+pub mod helper;
+
+pub mod confidential;
+
+pub mod wallet;
+
+pub mod keystore;
+
+pub mod config;
+
+pub mod funding;
@@ -1,326 +1,623 @@
-use anyhow::{Ok, Result};
-use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_sdk::{commitment_config::CommitmentConfig, signature::Keypair, signer::Signer};
-use spl_token_client::{
-    client::{ProgramRpcClient, ProgramRpcClientSendTransaction},
-    spl_token_2022::{self},
-    token::Token,
+use anyhow::{anyhow, Ok, Result};
+use clap::Parser;
+use confidential_solana::{
+    confidential::{audit_transfer, find_stale_proof_accounts, reclaim_proof_accounts},
+    config::RpcConfig,
+    funding::*,
+    helper::*,
+    keystore::read_passphrase,
+    rpc_program_client,
+    wallet::*,
+    ConfidentialClient,
 };
-use std::{io::stdin, sync::Arc};
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+use solana_signer::EncodableKey;
+use spl_token_client::spl_token_2022::solana_zk_sdk::encryption::{
+    elgamal::ElGamalKeypair, pod::elgamal::PodElGamalPubkey,
+};
+use std::time::Duration;
+
+pub mod cli;
+use cli::*;
 
-pub mod helper;
-use helper::*;
+/// Resolves the owner keypair, mint and token account from a saved wallet or explicit arguments.
+fn resolve_account(args: &AccountArgs) -> Result<(Keypair, Pubkey, Pubkey)> {
+    if let Some(name) = &args.wallet {
+        let wallet = Wallet::load(name)?;
+        let account = wallet.account.token_account;
+        return Ok((wallet.owner, wallet.mint, account));
+    }
 
-pub mod confidential;
-use confidential::*;
+    let owner = args
+        .owner
+        .as_ref()
+        .ok_or_else(|| anyhow!("--owner is required"))?;
+    let owner = read_keypair(owner)?;
+    let mint = args.mint.ok_or_else(|| anyhow!("--mint is required"))?;
+    // Without --account, the owner's associated token account is used
+    let account = args
+        .account
+        .unwrap_or_else(|| associated_token_address(&owner.pubkey(), &mint));
+
+    Ok((owner, mint, account))
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    println!("\n======== Creating Connection to Local Solana RPC ========");
-
-    let rpc_client = Arc::new(RpcClient::new_with_commitment(
-        String::from("http://localhost:8899"),
-        CommitmentConfig::confirmed(),
-    ));
-    println!("Connected to Solana RPC at localhost:8899");
-
-    println!("\n======== Generating Funded Keypairs for Alice and Bob ========");
-    let bob = keypair_gen(&rpc_client).await?;
-    let alice = keypair_gen(&rpc_client).await?;
-
-    println!(
-        "Generated Alice's and Bob's keypair: {} and {}",
-        alice.pubkey(),
-        bob.pubkey()
-    );
-
-    println!("\n======== Creating New Mint Account ========");
-    let mint_kp = Keypair::new(); // Mint Keypair
-    println!("Generated mint keypair: {}", mint_kp.pubkey());
-
-    // To interact with solana programs
-    let program_client = ProgramRpcClient::new(rpc_client.clone(), ProgramRpcClientSendTransaction);
-
-    // Helps us to interact with spl-token-programs
-    let token = Token::new(
-        Arc::new(program_client),         // Program Client
-        &spl_token_2022::ID,              // SPL Token Program 2022 Publickey
-        &mint_kp.pubkey(),                // Mint Address
-        Some(6),                          // Mint Decimal
-        Arc::new(alice.insecure_clone()), // Payer
-    );
-
-    // ======== Create Mint Account with ConfidentialTransferMint extension ========
-    create_confidential_mint(&alice.pubkey(), &[&mint_kp, &alice], &token).await?;
-
-    println!("\n========  Configure token account created for bob and alice ======= \n");
-    let alice_res = create_confidential_token_acc(&alice, &mint_kp, &rpc_client, &token).await?;
-    let bob_res = create_confidential_token_acc(&bob, &mint_kp, &rpc_client, &token).await?;
-
-    loop {
-        println!("\n================== 📝 Choose an instruction ==================\n");
-        println!("1️⃣  Check Token Account");
-        println!("2️⃣  Mint Tokens");
-        println!("3️⃣  Deposit & Apply Tokens Confidentially");
-        println!("4️⃣  Transfer Confidential Tokens");
-        println!("5️⃣  Withdraw Confidential Tokens");
-        println!("6️⃣  🚪 Exit");
-
-        let mut option = String::new();
-
-        stdin().read_line(&mut option).expect("❌ Invalid Input");
-        let option: i8 = option.trim().parse().expect("❌ Invalid Input");
-
-        match option {
-            1 => loop {
-                // For checking Token Accounts of Alice and Bob
-                println!("👤 Check Token Account for:");
-                println!("1️⃣  Alice");
-                println!("2️⃣  Bob");
-                let mut user = String::new();
-
-                stdin().read_line(&mut user).expect("❌ Invalid Input");
-                let user: i8 = user.trim().parse().expect("❌ Invalid Input");
-
-                match user {
-                    1 => {
-                        println!("🔍 Fetching Token Account Details for Alice...");
-                        fetch_token_account_with_extensions(
-                            &rpc_client,
-                            &alice_res.token_account_kp.pubkey(),
-                        )
-                        .await?;
-                        break;
-                    }
-                    2 => {
-                        println!("🔍 Fetching Token Account Details for Bob...");
-                        fetch_token_account_with_extensions(
-                            &rpc_client,
-                            &bob_res.token_account_kp.pubkey(),
-                        )
-                        .await?;
-                        break;
-                    }
-                    _ => {
-                        println!("❌ Invalid selection");
-                        break;
-                    }
-                }
-            },
-            2 => loop {
-                println!("👤 Mint tokens for:");
-                println!("1️⃣  Alice");
-                println!("2️⃣  Bob");
-                let mut user = String::new();
-
-                stdin().read_line(&mut user).expect("❌ Invalid Input");
-                let user: i8 = user.trim().parse().expect("❌ Invalid Input");
-
-                match user {
-                    1 => {
-                        println!("💸 Enter amount to mint for Alice:");
-                        let mut amount = String::new();
-                        stdin().read_line(&mut amount).expect("❌ Invalid input");
-
-                        let amount: u64 = amount.trim().parse().expect("❌ Invalid input");
-                        token
-                            .mint_to(
-                                &alice_res.token_account_kp.pubkey(), // Destination
-                                &alice.pubkey(),                      // Token Account authority
-                                amount * 10u64.pow(6),                // Minting tokens
-                                &[&alice],                            // Signers
-                            )
-                            .await?;
-
-                        println!("✅ Successfully minted {} tokens for Alice!", amount);
-                        break;
-                    }
-                    2 => {
-                        println!("💸 Enter amount to mint for Bob:");
-                        let mut amount = String::new();
-                        stdin().read_line(&mut amount).expect("❌ Invalid Input");
-
-                        let amount: u64 = amount.trim().parse().expect("❌ Invalid Input");
-                        token
-                            .mint_to(
-                                &bob_res.token_account_kp.pubkey(), // Destination
-                                &bob.pubkey(),                      // Token Account authority
-                                amount * 10u64.pow(6),              // Minting tokens
-                                &[&bob],                            // Signers
-                            )
-                            .await?;
-
-                        println!("✅ Successfully minted {} tokens for Bob!", amount);
-                        break;
-                    }
-                    _ => {
-                        println!("🚫 No tokens minted.");
-                        break;
-                    }
+    let cli = Cli::parse();
+
+    let rpc_config = RpcConfig::resolve(
+        cli.config.as_deref(),
+        cli.url.as_deref(),
+        cli.ws_url.as_deref(),
+        cli.commitment.as_deref(),
+    )?;
+    let rpc_client = rpc_config.rpc_client();
+    // Token transactions go through this program client, which only simulates them in a dry run
+    let program_client = rpc_program_client(rpc_client.clone(), cli.dry_run);
+    if cli.dry_run {
+        println!("🧪 Dry run: transactions are simulated, nothing is committed");
+    }
+
+    match cli.command {
+        Command::CreateMint {
+            authority,
+            mint_keypair,
+            decimals,
+            auditor,
+            manual_approval,
+            confidential_mint_burn,
+            transfer_fee_basis_points,
+            maximum_fee,
+        } => {
+            let authority = read_keypair(&authority)?;
+            let mint_kp = match mint_keypair {
+                Some(path) => read_keypair(&path)?,
+                None => Keypair::new(),
+            };
+            println!("Mint address: {}", mint_kp.pubkey());
+
+            let transfer_fee = match transfer_fee_basis_points {
+                Some(basis_points) => {
+                    let maximum_fee = match maximum_fee {
+                        Some(maximum_fee) => parse_token_amount(&maximum_fee, decimals)?,
+                        None => u64::MAX,
+                    };
+                    Some((basis_points, maximum_fee))
                 }
-            },
-            3 => {
-                println!("👤 Deposit confidential tokens for:");
-                println!("1️⃣  Alice");
-                println!("2️⃣  Bob");
-                let mut user = String::new();
-
-                stdin().read_line(&mut user).expect("❌ Invalid Input");
-                let user: i8 = user.trim().parse().expect("❌ Invalid Input");
-
-                println!("💰 Enter amount to deposit confidentially:");
-                let mut amount = String::new();
-                stdin().read_line(&mut amount).expect("❌ Invalid input");
-
-                let amount: u64 = amount.trim().parse().expect("❌ Invalid input");
-
-                match user {
-                    1 => {
-                        // Depositing tokens for Alice's pending account and apply pending account to available balance
-                        deposite_token_to_confidential(
-                            &alice_res.token_account_kp,
-                            &alice,
-                            &token,
-                            &alice_res.user_elgamal_kp,
-                            &alice_res.user_aes_kp,
-                            amount,
-                        )
-                        .await?;
-                        println!("✅ Deposited {} tokens confidentially for Alice.", amount);
-                    }
-                    2 => {
-                        // Depositing tokens for Bob's pending account and apply pending account to available balance
-                        deposite_token_to_confidential(
-                            &bob_res.token_account_kp,
-                            &bob,
-                            &token,
-                            &bob_res.user_elgamal_kp,
-                            &bob_res.user_aes_kp,
-                            amount,
-                        )
-                        .await?;
-                        println!("✅ Deposited {} tokens confidentially for Bob.", amount);
-                    }
-                    _ => {
-                        println!("❌ Invalid selection");
-                    }
+                None => None,
+            };
+
+            let client = ConfidentialClient::with_program_client(
+                program_client.clone(),
+                mint_kp.pubkey(),
+                authority,
+                decimals,
+            );
+
+            // ======== Create Mint Account with ConfidentialTransferMint extension ========
+            client
+                .create_mint(
+                    &mint_kp,
+                    auditor,
+                    !manual_approval,
+                    confidential_mint_burn,
+                    transfer_fee,
+                )
+                .await?;
+            println!("✅ Created confidential mint {}", mint_kp.pubkey());
+        }
+        Command::CreateAuditor { outfile } => {
+            let auditor = ElGamalKeypair::new_rand();
+            auditor
+                .write_to_file(&outfile)
+                .map_err(|e| anyhow!("Failed to write {}: {e}", outfile.display()))?;
+            println!("Wrote auditor ElGamal keypair to {}", outfile.display());
+            println!(
+                "Auditor ElGamal pubkey: {}",
+                PodElGamalPubkey::from(*auditor.pubkey())
+            );
+        }
+        Command::Audit {
+            auditor_keypair,
+            signature,
+        } => {
+            let auditor = read_elgamal_keypair(&auditor_keypair)?;
+            let transfers = audit_transfer(&rpc_client, &signature, &auditor).await?;
+            println!("✅ Decrypted {} confidential transfer(s)", transfers.len());
+        }
+        Command::CreateAccount {
+            mint,
+            owner,
+            wallet,
+            max_pending_credits,
+            associated,
+        } => {
+            let owner = read_keypair(&owner)?;
+            let client = ConfidentialClient::load_with_program_client(
+                program_client.clone(),
+                mint,
+                owner.insecure_clone(),
+            )
+            .await?;
+
+            let res = client
+                .create_account(&owner, max_pending_credits, associated)
+                .await?;
+            println!(
+                "✅ Created confidential token account {} for {}",
+                res.token_account,
+                owner.pubkey()
+            );
+
+            // A simulated account does not exist, so it is not saved
+            if let Some(name) = wallet.filter(|_| !cli.dry_run) {
+                let dir = Wallet::save(&name, &owner, &mint, &res)?;
+                println!("💾 Saved wallet {:?} to {}", name, dir.display());
+            }
+        }
+        Command::Recover {
+            owner,
+            account,
+            wallet,
+        } => {
+            let owner = read_keypair(&owner)?;
+            let mint = fetch_token_account_mint(&rpc_client, &account).await?;
+            let client = ConfidentialClient::load_with_program_client(
+                program_client.clone(),
+                mint,
+                owner.insecure_clone(),
+            )
+            .await?;
+
+            let res = client.recover_keys(&owner, &account).await?;
+            println!(
+                "✅ Recovered the confidential keys of {} from {}",
+                account,
+                owner.pubkey()
+            );
+
+            if let Some(name) = wallet {
+                let dir = Wallet::save(&name, &owner, &mint, &res)?;
+                println!("💾 Saved wallet {:?} to {}", name, dir.display());
+            }
+        }
+        Command::ApproveAccount {
+            mint,
+            authority,
+            account,
+        } => {
+            let authority = read_keypair(&authority)?;
+            let client = ConfidentialClient::load_with_program_client(
+                program_client.clone(),
+                mint,
+                authority.insecure_clone(),
+            )
+            .await?;
+
+            client.approve_account(&authority, &account).await?;
+            println!("✅ Approved confidential token account {}", account);
+        }
+        Command::Mint {
+            mint,
+            authority,
+            account,
+            amount,
+        } => {
+            let authority = read_keypair(&authority)?;
+            let client = ConfidentialClient::load_with_program_client(
+                program_client.clone(),
+                mint,
+                authority.insecure_clone(),
+            )
+            .await?;
+            let base_units = parse_token_amount(&amount, client.decimals())?;
+
+            client.mint_to(&authority, &account, base_units).await?;
+            println!("✅ Successfully minted {} tokens to {}!", amount, account);
+        }
+        Command::ConfidentialMint {
+            mint,
+            authority,
+            account,
+            amount,
+        } => {
+            let authority = read_keypair(&authority)?;
+            let client = ConfidentialClient::load_with_program_client(
+                program_client.clone(),
+                mint,
+                authority.insecure_clone(),
+            )
+            .await?;
+            let base_units = parse_token_amount(&amount, client.decimals())?;
+
+            client
+                .confidential_mint_to(&authority, &account, base_units)
+                .await?;
+            println!(
+                "✅ Minted {} tokens confidentially to {}! The owner must run `apply` to use them.",
+                amount, account
+            );
+        }
+        Command::Burn { account, amount } => {
+            let (owner, mint, account) = resolve_account(&account)?;
+            let client = ConfidentialClient::load_with_program_client(
+                program_client.clone(),
+                mint,
+                owner.insecure_clone(),
+            )
+            .await?;
+            let base_units = parse_token_amount(&amount, client.decimals())?;
+
+            client.burn(&owner, &account, base_units).await?;
+            println!(
+                "🔥 Burned {} tokens confidentially from {}.",
+                amount, account
+            );
+        }
+        Command::Deposit { account, amount } => {
+            let (owner, mint, account) = resolve_account(&account)?;
+            let client = ConfidentialClient::load_with_program_client(
+                program_client.clone(),
+                mint,
+                owner.insecure_clone(),
+            )
+            .await?;
+            let base_units = parse_token_amount(&amount, client.decimals())?;
+
+            // Depositing tokens to the pending balance and apply pending balance to available balance
+            client.deposit(&owner, &account, base_units).await?;
+            println!(
+                "✅ Deposited {} tokens confidentially to {}.",
+                amount, account
+            );
+        }
+        Command::Apply { account } => {
+            let (owner, mint, account) = resolve_account(&account)?;
+            let client = ConfidentialClient::load_with_program_client(
+                program_client.clone(),
+                mint,
+                owner.insecure_clone(),
+            )
+            .await?;
+
+            client.apply_pending(&owner, &account).await?;
+        }
+        Command::WatchPending {
+            account,
+            headroom,
+            interval,
+        } => {
+            let (owner, mint, account) = resolve_account(&account)?;
+            let client = ConfidentialClient::load_with_program_client(
+                program_client.clone(),
+                mint,
+                owner.insecure_clone(),
+            )
+            .await?;
+
+            // Runs until interrupted or an apply fails
+            client
+                .watch_pending(
+                    &owner,
+                    &account,
+                    headroom,
+                    Duration::from_secs(interval),
+                    Some(&rpc_config),
+                )
+                .await?;
+        }
+        Command::Transfer {
+            account,
+            recipient_wallet,
+            recipient_account,
+            amount,
+            inline_proofs,
+        } => {
+            let (owner, mint, account) = resolve_account(&account)?;
+            let recipient_account = match recipient_wallet {
+                Some(name) => Wallet::load(&name)?.account.token_account,
+                None => {
+                    recipient_account.ok_or_else(|| anyhow!("--recipient-account is required"))?
                 }
+            };
+            let client = ConfidentialClient::load_with_program_client(
+                program_client.clone(),
+                mint,
+                owner.insecure_clone(),
+            )
+            .await?;
+            let base_units = parse_token_amount(&amount, client.decimals())?;
+
+            println!(
+                "🔄 Transferring {} tokens confidentially from {} to {}...",
+                amount, account, recipient_account
+            );
+            if inline_proofs {
+                let inline = client
+                    .transfer_with_inline_proofs(&owner, &account, &recipient_account, base_units)
+                    .await?;
+                println!("Proofs verified inline: {}", inline);
+            } else {
+                client
+                    .transfer(&owner, &account, &recipient_account, base_units)
+                    .await?;
             }
-            4 => {
-                println!("👤 Transfer confidential tokens from:");
-                println!("1️⃣  Alice");
-                println!("2️⃣  Bob");
-                let mut user = String::new();
-
-                stdin().read_line(&mut user).expect("❌ Invalid Input");
-                let user: i8 = user.trim().parse().expect("❌ Invalid Input");
-
-                println!("🔄 Enter amount to transfer confidentially:");
-                let mut amount = String::new();
-                stdin().read_line(&mut amount).expect("❌ Invalid input");
-
-                let amount: u64 = amount.trim().parse().expect("❌ Invalid input");
-
-                match user {
-                    1 => {
-                        // Transfer Tokens Confidentially Alice to Bob
-                        println!(
-                            "🔄 Transferring {} tokens confidentially from Alice to Bob...",
-                            amount
-                        );
-                        transfer_tokens(
-                            amount,
-                            &token,
-                            &alice_res.token_account_kp,
-                            &alice_res.user_elgamal_kp,
-                            &alice_res.user_aes_kp,
-                            &alice,
-                            &bob,
-                            &bob_res.user_elgamal_kp,
-                            &bob_res.user_aes_kp,
-                            &bob_res.token_account_kp,
-                        )
-                        .await?;
-                        println!("✅ Transfer complete!");
-                    }
-                    2 => {
-                        // Transfer Tokens Confidentially Bob to Alice
-                        println!(
-                            "🔄 Transferring {} tokens confidentially from Bob to Alice...",
-                            amount
-                        );
-                        transfer_tokens(
-                            amount,
-                            &token,
-                            &bob_res.token_account_kp,
-                            &bob_res.user_elgamal_kp,
-                            &bob_res.user_aes_kp,
-                            &bob,
-                            &alice,
-                            &alice_res.user_elgamal_kp,
-                            &alice_res.user_aes_kp,
-                            &alice_res.token_account_kp,
-                        )
-                        .await?;
-                        println!("✅ Transfer complete!");
-                    }
-                    _ => {
-                        println!("❌ Invalid selection");
+            println!("✅ Transfer complete! The recipient must run `apply` to use the tokens.");
+        }
+        Command::Withdraw { account, amount } => {
+            let (owner, mint, account) = resolve_account(&account)?;
+            let client = ConfidentialClient::load_with_program_client(
+                program_client.clone(),
+                mint,
+                owner.insecure_clone(),
+            )
+            .await?;
+            let base_units = parse_token_amount(&amount, client.decimals())?;
+
+            client.withdraw(&owner, &account, base_units).await?;
+            println!(
+                "✅ Withdrawn {} tokens confidentially from {}.",
+                amount, account
+            );
+        }
+        Command::EnableConfidentialCredits { ref account }
+        | Command::DisableConfidentialCredits { ref account } => {
+            let enabled = matches!(cli.command, Command::EnableConfidentialCredits { .. });
+            let (owner, mint, account) = resolve_account(account)?;
+            let client = ConfidentialClient::load_with_program_client(
+                program_client.clone(),
+                mint,
+                owner.insecure_clone(),
+            )
+            .await?;
+
+            client
+                .set_confidential_credits(&owner, &account, enabled)
+                .await?;
+            println!(
+                "✅ Confidential credits {} for {}",
+                if enabled { "enabled" } else { "disabled" },
+                account
+            );
+        }
+        Command::EnableNonConfidentialCredits { ref account }
+        | Command::DisableNonConfidentialCredits { ref account } => {
+            let enabled = matches!(cli.command, Command::EnableNonConfidentialCredits { .. });
+            let (owner, mint, account) = resolve_account(account)?;
+            let client = ConfidentialClient::load_with_program_client(
+                program_client.clone(),
+                mint,
+                owner.insecure_clone(),
+            )
+            .await?;
+
+            client
+                .set_non_confidential_credits(&owner, &account, enabled)
+                .await?;
+            println!(
+                "✅ Non-confidential credits {} for {}",
+                if enabled { "enabled" } else { "disabled" },
+                account
+            );
+        }
+        Command::CloseAccount { account } => {
+            let (owner, mint, account) = resolve_account(&account)?;
+            let client = ConfidentialClient::load_with_program_client(
+                program_client.clone(),
+                mint,
+                owner.insecure_clone(),
+            )
+            .await?;
+
+            client.close_account(&owner, &account).await?;
+            println!("✅ Closed confidential token account {}", account);
+        }
+        Command::Rotate {
+            account,
+            new_wallet,
+            allow_transfer_fee,
+        } => {
+            let (owner, mint, account) = resolve_account(&account)?;
+            let client = ConfidentialClient::load_with_program_client(
+                program_client.clone(),
+                mint,
+                owner.insecure_clone(),
+            )
+            .await?;
+
+            let res = client
+                .rotate_account(&owner, &account, allow_transfer_fee)
+                .await?;
+            println!(
+                "✅ Rotated the ElGamal key of {}, the new account is {}",
+                account, res.token_account
+            );
+
+            // A simulated account does not exist, so it is not saved
+            if let Some(name) = new_wallet.filter(|_| !cli.dry_run) {
+                let dir = Wallet::save(&name, &owner, &mint, &res)?;
+                println!("💾 Saved wallet {:?} to {}", name, dir.display());
+            }
+        }
+        Command::HarvestFees {
+            mint,
+            payer,
+            accounts,
+        } => {
+            let payer = read_keypair(&payer)?;
+            let client =
+                ConfidentialClient::load_with_program_client(program_client.clone(), mint, payer)
+                    .await?;
+
+            client.harvest_fees(&accounts).await?;
+            println!(
+                "✅ Harvested withheld fees of {} account(s) to the mint {}",
+                accounts.len(),
+                mint
+            );
+        }
+        Command::WithdrawFees {
+            mint,
+            authority,
+            account,
+        } => {
+            let authority = read_keypair(&authority)?;
+            let client = ConfidentialClient::load_with_program_client(
+                program_client.clone(),
+                mint,
+                authority.insecure_clone(),
+            )
+            .await?;
+
+            let amount = client.withdraw_fees(&authority, &account).await?;
+            println!(
+                "✅ Withdrew {} tokens of withheld fees to {}",
+                format_token_amount(amount, client.decimals()),
+                account
+            );
+        }
+        Command::Show {
+            wallet,
+            account,
+            owner,
+        } => {
+            // Resolve the token account and, if the owner is known, its confidential keys
+            let (account, keys) = match wallet {
+                Some(name) => {
+                    let wallet = Wallet::load(&name)?;
+                    let account = wallet.account;
+                    (
+                        account.token_account,
+                        Some((account.user_elgamal_kp, account.user_aes_kp)),
+                    )
+                }
+                None => {
+                    let account = account.ok_or_else(|| anyhow!("--account is required"))?;
+                    let keys = match owner {
+                        Some(path) => {
+                            Some(derive_confidential_keys(&read_keypair(&path)?, &account)?)
+                        }
+                        None => None,
+                    };
+                    (account, keys)
+                }
+            };
+
+            println!("🔍 Fetching Token Account Details for {}...", account);
+            fetch_token_account_with_extensions(
+                &rpc_client,
+                &account,
+                keys.as_ref()
+                    .map(|(elgamal_kp, aes_kp)| (elgamal_kp, aes_kp)),
+            )
+            .await?;
+        }
+        Command::Wallets => {
+            println!("Wallet store: {}", wallet_root()?.display());
+            for (name, metadata) in Wallet::list()? {
+                println!(
+                    "- {}: owner {}, token account {}, mint {}{}",
+                    name,
+                    metadata.owner,
+                    metadata.token_account,
+                    metadata.mint,
+                    if Wallet::is_locked(&name)? {
+                        " (locked)"
+                    } else {
+                        ""
                     }
+                );
+            }
+        }
+        Command::Lock { wallet } => {
+            let passphrase = read_passphrase("New passphrase: ", true)?;
+            let dir = Wallet::lock(&wallet, &passphrase)?;
+            println!("🔒 Locked wallet {:?} in {}", wallet, dir.display());
+        }
+        Command::Unlock { wallet } => {
+            let passphrase = read_passphrase("Passphrase: ", false)?;
+            let dir = Wallet::unlock(&wallet, &passphrase)?;
+            println!("🔓 Unlocked wallet {:?} in {}", wallet, dir.display());
+        }
+        Command::Fund {
+            recipients,
+            sol,
+            funder,
+            airdrop_timeout,
+        } => {
+            let lamports = parse_token_amount(&sol, 9)?;
+            let source = match funder {
+                Some(path) => FundingSource::Funder(Box::new(read_keypair(&path)?)),
+                None => FundingSource::Airdrop {
+                    deadline: Duration::from_secs(airdrop_timeout),
+                },
+            };
+            // Airdrops cannot be simulated, so a dry run only lists the recipients
+            if cli.dry_run {
+                for recipient in &recipients {
+                    println!("🧪 Would fund {} up to {} SOL", recipient, sol);
                 }
+                return Ok(());
             }
-            5 => {
-                println!("👤 Withdraw confidential tokens for:");
-                println!("1️⃣  Alice");
-                println!("2️⃣  Bob");
-                let mut user = String::new();
-
-                stdin().read_line(&mut user).expect("❌ Invalid Input");
-                let user: i8 = user.trim().parse().expect("❌ Invalid Input");
-
-                println!("🏧 Enter amount to withdraw confidentially:");
-                let mut amount = String::new();
-                stdin().read_line(&mut amount).expect("❌ Invalid input");
-
-                let amount: u64 = amount.trim().parse().expect("❌ Invalid input");
-
-                match user {
-                    1 => {
-                        withdraw_tokens(
-                            &alice_res.token_account_kp.pubkey(),
-                            &alice_res.user_elgamal_kp,
-                            &alice_res.user_aes_kp,
-                            amount,
-                            &token,
-                            &alice,
-                        )
-                        .await?;
-                        println!("✅ Withdrawn {} tokens confidentially for Alice.", amount);
-                    }
-                    2 => {
-                        withdraw_tokens(
-                            &bob_res.token_account_kp.pubkey(),
-                            &bob_res.user_elgamal_kp,
-                            &bob_res.user_aes_kp,
-                            amount,
-                            &token,
-                            &bob,
-                        )
-                        .await?;
-                        println!("✅ Withdrawn {} tokens confidentially for Bob.", amount);
-                    }
-                    _ => {
-                        println!("❌ Invalid selection");
-                    }
+
+            for recipient in &recipients {
+                fund_account(&rpc_client, recipient, lamports, &source).await?;
+            }
+            println!("✅ Funded {} address(es)", recipients.len());
+        }
+        Command::ReclaimProofAccounts {
+            wallet,
+            authority,
+            min_age,
+            include_unknown,
+        } => {
+            let authority = match wallet {
+                Some(name) => Wallet::load(&name)?.owner,
+                None => {
+                    read_keypair(&authority.ok_or_else(|| anyhow!("--authority is required"))?)?
                 }
+            };
+
+            let stale_accounts = find_stale_proof_accounts(
+                &rpc_client,
+                &authority.pubkey(),
+                Duration::from_secs(min_age),
+                include_unknown,
+            )
+            .await?;
+            if stale_accounts.is_empty() {
+                println!(
+                    "✅ No stale proof context state accounts of {}",
+                    authority.pubkey()
+                );
+                return Ok(());
             }
-            6 => {
-                println!("👋 Exiting. Goodbye!");
-                break;
+
+            let rent: u64 = stale_accounts.iter().map(|account| account.lamports).sum();
+            println!(
+                "Found {} stale proof context state account(s) holding {} SOL:",
+                stale_accounts.len(),
+                format_token_amount(rent, 9)
+            );
+            for account in &stale_accounts {
+                println!("- {} ({} lamports)", account.address, account.lamports);
             }
-            _ => {
-                println!("❌ Invalid option. Please try again.");
+            // The closes are sent straight through the RPC client, a dry run stops at the list
+            if cli.dry_run {
+                return Ok(());
             }
+
+            let addresses: Vec<_> = stale_accounts
+                .iter()
+                .map(|account| account.address)
+                .collect();
+            reclaim_proof_accounts(&rpc_client, &authority, &addresses).await?;
+            println!(
+                "✅ Reclaimed {} SOL to {}",
+                format_token_amount(rent, 9),
+                authority.pubkey()
+            );
         }
     }
 
@@ -0,0 +1,287 @@
+use anyhow::{anyhow, bail, Ok, Result};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{write_keypair_file, Keypair},
+    signer::Signer,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{
+    helper::{
+        associated_token_address, derive_confidential_keys, read_keypair, ConfTokenAccountRes,
+    },
+    keystore::{read_passphrase, Keystore, KeystoreSecrets},
+};
+
+// =================== Constants ===================
+
+const OWNER_FILE: &str = "owner.json";
+const TOKEN_ACCOUNT_FILE: &str = "token-account.json";
+const METADATA_FILE: &str = "wallet.json";
+const KEYSTORE_FILE: &str = "keystore.json";
+
+/// Method used by `derive_confidential_keys` for both the ElGamal keypair and the AE key.
+const DERIVATION_METHOD: &str = "new_from_signer(owner, token_account.to_bytes())";
+
+// =================== Structs ===================
+
+/// Describes how the confidential keys of a wallet were derived, so they can be recreated.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct KeyDerivation {
+    pub method: String, // Derivation function applied to the signer and seed
+    pub signer: String, // Pubkey of the keypair that signs the seed
+    pub seed: String,   // Pubkey whose bytes are signed (the token account address)
+}
+
+/// Contents of `wallet.json`, the public part of a saved wallet.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WalletMetadata {
+    pub owner: String,
+    pub token_account: String,
+    pub mint: String,
+    #[serde(default)]
+    pub associated: bool, // Token account is the owner's associated token account (no keypair file)
+    pub elgamal_derivation: KeyDerivation,
+    pub ae_derivation: KeyDerivation,
+}
+
+/// A confidential token account saved on disk, with its owner and recreated keys.
+pub struct Wallet {
+    pub name: String,
+    pub owner: Keypair,               // Owner (authority) of the token account
+    pub mint: Pubkey,                 // Mint of the token account
+    pub account: ConfTokenAccountRes, // Token account and its confidential keys
+}
+
+// =================== Wallet Store ===================
+
+/// Root directory of the wallet store: `~/.confidential-solana/wallets`.
+pub fn wallet_root() -> Result<PathBuf> {
+    let home = dirs::home_dir().ok_or_else(|| anyhow!("Unable to locate home directory"))?;
+    Ok(home.join(".confidential-solana").join("wallets"))
+}
+
+/// Directory of a single wallet inside the wallet store.
+fn wallet_dir(name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        bail!("Invalid wallet name: {name:?}");
+    }
+    Ok(wallet_root()?.join(name))
+}
+
+impl Wallet {
+    /// Saves the owner keypair, token account keypair (if there is one, e.g. not for the
+    /// owner's associated token account or a recovered account) and mint under `name`.
+    /// Fails if a wallet with the same name already exists.
+    pub fn save(
+        name: &str,
+        owner: &Keypair,
+        mint: &Pubkey,
+        account: &ConfTokenAccountRes,
+    ) -> Result<PathBuf> {
+        let dir = wallet_dir(name)?;
+        if dir.exists() {
+            bail!("Wallet {name:?} already exists at {}", dir.display());
+        }
+        fs::create_dir_all(&dir)?;
+
+        write_keypair(owner, &dir.join(OWNER_FILE))?;
+        if let Some(token_account_kp) = &account.token_account_kp {
+            write_keypair(token_account_kp, &dir.join(TOKEN_ACCOUNT_FILE))?;
+        }
+
+        let token_account = account.token_account;
+        let derivation = || KeyDerivation {
+            method: String::from(DERIVATION_METHOD),
+            signer: owner.pubkey().to_string(),
+            seed: token_account.to_string(),
+        };
+        let metadata = WalletMetadata {
+            owner: owner.pubkey().to_string(),
+            token_account: token_account.to_string(),
+            mint: mint.to_string(),
+            associated: token_account == associated_token_address(&owner.pubkey(), mint),
+            elgamal_derivation: derivation(),
+            ae_derivation: derivation(),
+        };
+        fs::write(
+            dir.join(METADATA_FILE),
+            serde_json::to_string_pretty(&metadata)?,
+        )?;
+
+        Ok(dir)
+    }
+
+    /// Loads a saved wallet and recreates its ElGamal keypair and AE key.
+    /// A locked wallet is decrypted with the passphrase from `CONFIDENTIAL_SOLANA_PASSPHRASE`
+    /// or the terminal.
+    pub fn load(name: &str) -> Result<Wallet> {
+        let dir = wallet_dir(name)?;
+        if !dir.exists() {
+            bail!("Wallet {name:?} not found in {}", wallet_root()?.display());
+        }
+
+        let metadata = read_metadata(&dir)?;
+
+        // Locked wallets keep the owner keypair and the confidential keys in the keystore
+        let keystore_file = dir.join(KEYSTORE_FILE);
+        let (owner, keys) = if keystore_file.exists() {
+            let passphrase = read_passphrase(&format!("Passphrase of wallet {name:?}: "), false)?;
+            let secrets = Keystore::read(&keystore_file)?.decrypt(&passphrase)?;
+            (secrets.owner, Some((secrets.elgamal_kp, secrets.aes_kp)))
+        } else {
+            (read_keypair(&dir.join(OWNER_FILE))?, None)
+        };
+        let mint = Pubkey::from_str(&metadata.mint)?;
+
+        // Associated token accounts are found from the owner and mint alone, accounts
+        // without a keypair file (e.g. recovered ones) by their recorded address
+        let token_account_file = dir.join(TOKEN_ACCOUNT_FILE);
+        let (token_account, token_account_kp) = if metadata.associated {
+            (associated_token_address(&owner.pubkey(), &mint), None)
+        } else if token_account_file.exists() {
+            let token_account_kp = read_keypair(&token_account_file)?;
+            (token_account_kp.pubkey(), Some(token_account_kp))
+        } else {
+            (Pubkey::from_str(&metadata.token_account)?, None)
+        };
+
+        // The keypair files must match the recorded addresses
+        if owner.pubkey().to_string() != metadata.owner {
+            bail!(
+                "Owner keypair of wallet {name:?} does not match {}",
+                metadata.owner
+            );
+        }
+        if token_account.to_string() != metadata.token_account {
+            bail!(
+                "Token account of wallet {name:?} does not match {}",
+                metadata.token_account
+            );
+        }
+
+        // Only keys derived from the owner over the token account can be recreated
+        for derivation in [&metadata.elgamal_derivation, &metadata.ae_derivation] {
+            if derivation.method != DERIVATION_METHOD
+                || derivation.signer != metadata.owner
+                || derivation.seed != metadata.token_account
+            {
+                bail!("Unsupported key derivation in wallet {name:?}: {derivation:?}");
+            }
+        }
+
+        let (user_elgamal_kp, user_aes_kp) = match keys {
+            Some(keys) => keys,
+            None => derive_confidential_keys(&owner, &token_account)?,
+        };
+
+        Ok(Wallet {
+            name: String::from(name),
+            owner,
+            mint,
+            account: ConfTokenAccountRes {
+                token_account,
+                token_account_kp,
+                user_elgamal_kp,
+                user_aes_kp,
+            },
+        })
+    }
+
+    /// Encrypts the owner keypair, ElGamal keypair and AE key of a wallet with `passphrase`
+    /// into `keystore.json` and removes the plaintext owner keypair.
+    pub fn lock(name: &str, passphrase: &str) -> Result<PathBuf> {
+        if Self::is_locked(name)? {
+            bail!("Wallet {name:?} is already locked");
+        }
+
+        let wallet = Self::load(name)?;
+        let secrets = KeystoreSecrets {
+            owner: wallet.owner,
+            elgamal_kp: wallet.account.user_elgamal_kp,
+            aes_kp: wallet.account.user_aes_kp,
+        };
+
+        let dir = wallet_dir(name)?;
+        Keystore::encrypt(&secrets, passphrase)?.write(&dir.join(KEYSTORE_FILE))?;
+        fs::remove_file(dir.join(OWNER_FILE))?;
+
+        Ok(dir)
+    }
+
+    /// Decrypts the keystore of a locked wallet and restores the plaintext owner keypair.
+    pub fn unlock(name: &str, passphrase: &str) -> Result<PathBuf> {
+        if !Self::is_locked(name)? {
+            bail!("Wallet {name:?} is not locked");
+        }
+
+        let dir = wallet_dir(name)?;
+        let keystore_file = dir.join(KEYSTORE_FILE);
+        let secrets = Keystore::read(&keystore_file)?.decrypt(passphrase)?;
+
+        let metadata = read_metadata(&dir)?;
+        if secrets.owner.pubkey().to_string() != metadata.owner {
+            bail!(
+                "Owner keypair in the keystore of wallet {name:?} does not match {}",
+                metadata.owner
+            );
+        }
+
+        write_keypair(&secrets.owner, &dir.join(OWNER_FILE))?;
+        fs::remove_file(keystore_file)?;
+
+        Ok(dir)
+    }
+
+    /// Whether the secrets of a wallet are encrypted in a keystore.
+    pub fn is_locked(name: &str) -> Result<bool> {
+        let dir = wallet_dir(name)?;
+        if !dir.exists() {
+            bail!("Wallet {name:?} not found in {}", wallet_root()?.display());
+        }
+        Ok(dir.join(KEYSTORE_FILE).exists())
+    }
+
+    /// Lists the names and metadata of all saved wallets.
+    pub fn list() -> Result<Vec<(String, WalletMetadata)>> {
+        let root = wallet_root()?;
+        if !root.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut wallets = Vec::new();
+        for entry in fs::read_dir(root)? {
+            let path = entry?.path();
+            if !path.join(METADATA_FILE).exists() {
+                continue;
+            }
+            let name = path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned();
+            wallets.push((name, read_metadata(&path)?));
+        }
+        wallets.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Ok(wallets)
+    }
+}
+
+fn read_metadata(dir: &Path) -> Result<WalletMetadata> {
+    let path = dir.join(METADATA_FILE);
+    let data =
+        fs::read_to_string(&path).map_err(|e| anyhow!("Failed to read {}: {e}", path.display()))?;
+    Ok(serde_json::from_str(&data)?)
+}
+
+fn write_keypair(keypair: &Keypair, path: &Path) -> Result<()> {
+    write_keypair_file(keypair, path)
+        .map_err(|e| anyhow!("Failed to write keypair {}: {e}", path.display()))?;
+    Ok(())
+}
@@ -0,0 +1,1253 @@
+//! End-to-end confidential token flow on an in-process bank (`solana-program-test`),
+//! without a running validator.
+
+use confidential_solana::{
+    confidential::{find_confidential_transfers, DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER},
+    config::RpcConfig,
+    funding::{fund_account, FundingSource},
+    helper::{
+        associated_token_address, derive_confidential_keys, parse_token_amount, ConfidentialSender,
+        ProgramBanksClientProcessWithPreflight, ProgramBanksClientSimulate,
+    },
+    keystore::{Keystore, KeystoreSecrets},
+    ConfidentialClient, ConfidentialError,
+};
+use solana_banks_interface::BanksTransactionResultWithSimulation;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program_test::{
+    processor, tokio::sync::Mutex, BanksClient, ProgramTest, ProgramTestContext,
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+    transaction::{Transaction, VersionedTransaction},
+};
+use solana_system_interface::instruction as system_instruction;
+use spl_token_client::{
+    client::{
+        ProgramBanksClient, ProgramClientResult, SendTransaction, SendTransactionBanksClient,
+        SimulateTransaction, SimulateTransactionBanksClient,
+    },
+    spl_token_2022::{
+        self,
+        instruction::AuthorityType,
+        solana_zk_sdk::encryption::{elgamal::ElGamalKeypair, pod::elgamal::PodElGamalPubkey},
+    },
+};
+use std::{env, fs, future::Future, pin::Pin, sync, sync::Arc, time::Duration};
+
+const DECIMALS: u8 = 6;
+const TOKEN: u64 = 10u64.pow(DECIMALS as u32);
+
+type BanksConfidentialClient = ConfidentialClient<ProgramBanksClientProcessWithPreflight>;
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Processes transactions like `ProgramBanksClientProcessWithPreflight` and keeps a copy of
+/// each, since the bank does not return the instruction data of processed transactions.
+#[derive(Clone, Default)]
+struct RecordingSender {
+    transactions: Arc<sync::Mutex<Vec<Transaction>>>,
+}
+
+impl SendTransaction for RecordingSender {
+    type Output = ();
+}
+
+impl SendTransactionBanksClient for RecordingSender {
+    fn send<'a>(
+        &self,
+        client: &'a mut BanksClient,
+        transaction: Transaction,
+    ) -> BoxFuture<'a, ProgramClientResult<Self::Output>> {
+        self.transactions.lock().unwrap().push(transaction.clone());
+        ProgramBanksClientProcessWithPreflight.send(client, transaction)
+    }
+}
+
+impl SimulateTransaction for RecordingSender {
+    type SimulationOutput = BanksTransactionResultWithSimulation;
+}
+
+impl SimulateTransactionBanksClient for RecordingSender {
+    fn simulate<'a>(
+        &self,
+        client: &'a mut BanksClient,
+        transaction: Transaction,
+    ) -> BoxFuture<'a, ProgramClientResult<Self::SimulationOutput>> {
+        ProgramBanksClientProcessWithPreflight.simulate(client, transaction)
+    }
+}
+
+/// Starts a bank and creates a confidential mint whose mint authority is the bank's payer.
+async fn setup() -> (Arc<Mutex<ProgramTestContext>>, BanksConfidentialClient) {
+    setup_with(false, None).await
+}
+
+/// Like [`setup`], optionally adding the ConfidentialMintBurn extension or a transfer fee
+/// (basis points, maximum fee) to the mint.
+async fn setup_with(
+    confidential_mint_burn: bool,
+    transfer_fee: Option<(u16, u64)>,
+) -> (Arc<Mutex<ProgramTestContext>>, BanksConfidentialClient) {
+    setup_on(
+        native_token_2022(),
+        ProgramBanksClientProcessWithPreflight,
+        None,
+        confidential_mint_burn,
+        transfer_fee,
+    )
+    .await
+}
+
+/// Like [`setup_with`], with `auditor` as the mint's auditor and a client that records the
+/// transactions it sends.
+async fn setup_audited(
+    auditor: &ElGamalKeypair,
+    transfer_fee: Option<(u16, u64)>,
+) -> (
+    Arc<Mutex<ProgramTestContext>>,
+    ConfidentialClient<RecordingSender>,
+    Arc<sync::Mutex<Vec<Transaction>>>,
+) {
+    let sender = RecordingSender::default();
+    let transactions = sender.transactions.clone();
+    let (context, client) = setup_on(
+        native_token_2022(),
+        sender,
+        Some(PodElGamalPubkey::from(*auditor.pubkey())),
+        false,
+        transfer_fee,
+    )
+    .await;
+
+    (context, client, transactions)
+}
+
+/// A bank running Token-2022 natively from the crate the client is built against: the bundled
+/// program binary predates the current proof format of confidential transfers with fee.
+fn native_token_2022() -> ProgramTest {
+    let mut program_test = ProgramTest::default();
+    program_test.prefer_bpf(false);
+    program_test.add_program(
+        "spl_token_2022",
+        spl_token_2022::id(),
+        processor!(spl_token_2022::processor::Processor::process),
+    );
+    program_test
+}
+
+/// Starts `program_test` and creates a confidential mint whose mint authority is the bank's payer.
+async fn setup_on<S>(
+    program_test: ProgramTest,
+    sender: S,
+    auditor: Option<PodElGamalPubkey>,
+    confidential_mint_burn: bool,
+    transfer_fee: Option<(u16, u64)>,
+) -> (Arc<Mutex<ProgramTestContext>>, ConfidentialClient<S>)
+where
+    S: ConfidentialSender + SendTransactionBanksClient + SimulateTransactionBanksClient,
+    S: Send + Sync + 'static,
+{
+    let context = program_test.start_with_context().await;
+    let payer = context.payer.insecure_clone();
+    let context = Arc::new(Mutex::new(context));
+
+    let program_client = Arc::new(ProgramBanksClient::new_from_context(
+        context.clone(),
+        sender,
+    ));
+
+    let mint_kp = Keypair::new();
+    let client =
+        ConfidentialClient::with_program_client(program_client, mint_kp.pubkey(), payer, DECIMALS);
+    client
+        .create_mint(
+            &mint_kp,
+            auditor,
+            true,
+            confidential_mint_burn,
+            transfer_fee,
+        )
+        .await
+        .unwrap();
+
+    (context, client)
+}
+
+/// Creates a keypair funded with 1 SOL by the bank's payer.
+async fn funded_keypair(context: &Mutex<ProgramTestContext>) -> Keypair {
+    let keypair = Keypair::new();
+    let context = context.lock().await;
+
+    let transfer_ix =
+        system_instruction::transfer(&context.payer.pubkey(), &keypair.pubkey(), LAMPORTS_PER_SOL);
+    let transaction = Transaction::new_signed_with_payer(
+        &[transfer_ix],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        context.last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    keypair
+}
+
+/// Checks the public balance and the decrypted pending and available balances of an account.
+async fn assert_balances<T: ConfidentialSender>(
+    client: &ConfidentialClient<T>,
+    owner: &Keypair,
+    account: &Pubkey,
+    public: u64,
+    pending: u64,
+    available: u64,
+) {
+    let balances = client.confidential_balances(owner, account).await.unwrap();
+    assert_eq!(client.public_balance(account).await.unwrap(), public);
+    assert_eq!(balances.pending, pending);
+    assert_eq!(balances.available, available);
+}
+
+#[tokio::test]
+async fn mint_deposit_transfer_withdraw() {
+    let (context, client) = setup().await;
+    let alice = funded_keypair(&context).await;
+    let bob = funded_keypair(&context).await;
+
+    // Accounts start empty
+    let alice_account = client
+        .create_account(
+            &alice,
+            DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER,
+            false,
+        )
+        .await
+        .unwrap()
+        .token_account;
+    let bob_account = client
+        .create_account(&bob, DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER, false)
+        .await
+        .unwrap()
+        .token_account;
+    assert_balances(&client, &alice, &alice_account, 0, 0, 0).await;
+    assert_balances(&client, &bob, &bob_account, 0, 0, 0).await;
+
+    // Mint public tokens to Alice
+    client
+        .mint_to(client.payer(), &alice_account, 100 * TOKEN)
+        .await
+        .unwrap();
+    assert_balances(&client, &alice, &alice_account, 100 * TOKEN, 0, 0).await;
+
+    // Deposit into the confidential balance (deposit also applies the pending balance)
+    client
+        .deposit(&alice, &alice_account, 50 * TOKEN)
+        .await
+        .unwrap();
+    assert_balances(&client, &alice, &alice_account, 50 * TOKEN, 0, 50 * TOKEN).await;
+
+    // Transfer lands in Bob's pending balance
+    client
+        .transfer(&alice, &alice_account, &bob_account, 10 * TOKEN)
+        .await
+        .unwrap();
+    assert_balances(&client, &alice, &alice_account, 50 * TOKEN, 0, 40 * TOKEN).await;
+    assert_balances(&client, &bob, &bob_account, 0, 10 * TOKEN, 0).await;
+
+    // Bob applies his pending balance
+    client.apply_pending(&bob, &bob_account).await.unwrap();
+    assert_balances(&client, &bob, &bob_account, 0, 0, 10 * TOKEN).await;
+
+    // Bob withdraws back to his public balance
+    client
+        .withdraw(&bob, &bob_account, 5 * TOKEN)
+        .await
+        .unwrap();
+    assert_balances(&client, &bob, &bob_account, 5 * TOKEN, 0, 5 * TOKEN).await;
+}
+
+#[tokio::test]
+async fn transfer_with_inline_proofs() {
+    let (context, client) = setup().await;
+    let alice = funded_keypair(&context).await;
+    let bob = funded_keypair(&context).await;
+
+    let alice_account = client
+        .create_account(
+            &alice,
+            DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER,
+            false,
+        )
+        .await
+        .unwrap()
+        .token_account;
+    let bob_account = client
+        .create_account(&bob, DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER, false)
+        .await
+        .unwrap()
+        .token_account;
+    client
+        .mint_to(client.payer(), &alice_account, 20 * TOKEN)
+        .await
+        .unwrap();
+    client
+        .deposit(&alice, &alice_account, 20 * TOKEN)
+        .await
+        .unwrap();
+
+    // Both proofs together exceed the packet size, the equality proof alone fits
+    let inline_proofs = client
+        .transfer_with_inline_proofs(&alice, &alice_account, &bob_account, 7 * TOKEN)
+        .await
+        .unwrap();
+    assert_eq!(inline_proofs, 1);
+    assert_balances(&client, &alice, &alice_account, 0, 0, 13 * TOKEN).await;
+    assert_balances(&client, &bob, &bob_account, 0, 7 * TOKEN, 0).await;
+}
+
+#[tokio::test]
+async fn failed_transfer_closes_proof_accounts() {
+    let (context, client) = setup().await;
+    let alice = funded_keypair(&context).await;
+    let bob = funded_keypair(&context).await;
+
+    let alice_account = client
+        .create_account(
+            &alice,
+            DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER,
+            false,
+        )
+        .await
+        .unwrap()
+        .token_account;
+    client
+        .mint_to(client.payer(), &alice_account, 10 * TOKEN)
+        .await
+        .unwrap();
+    client
+        .deposit(&alice, &alice_account, 10 * TOKEN)
+        .await
+        .unwrap();
+
+    // Bob's account accepts a single credit, which his own deposit uses up
+    let bob_account = client
+        .create_account(&bob, 1, false)
+        .await
+        .unwrap()
+        .token_account;
+    client
+        .mint_to(client.payer(), &bob_account, TOKEN)
+        .await
+        .unwrap();
+    assert!(deposit_without_apply(&client, &bob, &bob_account, TOKEN).await);
+
+    // Alice's public balance is empty, her confidential balance holds the 10 tokens
+    assert!(matches!(
+        client.deposit(&alice, &alice_account, TOKEN).await,
+        Err(ConfidentialError::InsufficientPublicBalance)
+    ));
+    let public_transfer = client
+        .token()
+        .transfer(
+            &alice_account,
+            &bob_account,
+            &alice.pubkey(),
+            TOKEN,
+            &[&alice],
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        ConfidentialError::from(public_transfer),
+        ConfidentialError::InsufficientPublicBalance
+    ));
+    assert!(matches!(
+        client.withdraw(&alice, &alice_account, 11 * TOKEN).await,
+        Err(ConfidentialError::InsufficientBalance)
+    ));
+
+    // The transfer fails on-chain (MaximumPendingBalanceCreditCounterExceeded), after all
+    // proof context state accounts were created
+    let alice_lamports = || async {
+        let context = context.lock().await;
+        context
+            .banks_client
+            .get_balance(alice.pubkey())
+            .await
+            .unwrap()
+    };
+    let lamports_before = alice_lamports().await;
+    assert!(matches!(
+        client
+            .transfer(&alice, &alice_account, &bob_account, 5 * TOKEN)
+            .await,
+        Err(ConfidentialError::PendingCreditCounterExceeded)
+    ));
+
+    // The accounts were closed anyway and their rent went to Alice, the payer paid the fees
+    assert!(alice_lamports().await > lamports_before);
+    assert_balances(&client, &alice, &alice_account, 0, 0, 10 * TOKEN).await;
+}
+
+#[tokio::test]
+async fn confidential_mint_and_burn() {
+    let (context, client) = setup_with(true, None).await;
+    let alice = funded_keypair(&context).await;
+
+    let alice_account = client
+        .create_account(
+            &alice,
+            DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER,
+            false,
+        )
+        .await
+        .unwrap()
+        .token_account;
+
+    // Minted tokens land in the pending balance, never in the public balance
+    client
+        .confidential_mint_to(client.payer(), &alice_account, 100 * TOKEN)
+        .await
+        .unwrap();
+    assert_balances(&client, &alice, &alice_account, 0, 100 * TOKEN, 0).await;
+
+    client.apply_pending(&alice, &alice_account).await.unwrap();
+    assert_balances(&client, &alice, &alice_account, 0, 0, 100 * TOKEN).await;
+
+    // Burning comes out of the available balance
+    client
+        .burn(&alice, &alice_account, 30 * TOKEN)
+        .await
+        .unwrap();
+    assert_balances(&client, &alice, &alice_account, 0, 0, 70 * TOKEN).await;
+}
+
+#[tokio::test]
+async fn transfer_with_fee_and_withdraw_fees() {
+    // 1% transfer fee, capped at 1 token
+    let (context, client) = setup_with(false, Some((100, TOKEN))).await;
+    let alice = funded_keypair(&context).await;
+    let bob = funded_keypair(&context).await;
+
+    let alice_account = client
+        .create_account(
+            &alice,
+            DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER,
+            false,
+        )
+        .await
+        .unwrap()
+        .token_account;
+    let bob_account = client
+        .create_account(&bob, DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER, false)
+        .await
+        .unwrap()
+        .token_account;
+    // The mint authority is the withdraw withheld authority and owns the fee account
+    let fee_account = client
+        .create_account(
+            client.payer(),
+            DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER,
+            false,
+        )
+        .await
+        .unwrap()
+        .token_account;
+
+    client
+        .mint_to(client.payer(), &alice_account, 100 * TOKEN)
+        .await
+        .unwrap();
+    client
+        .deposit(&alice, &alice_account, 100 * TOKEN)
+        .await
+        .unwrap();
+
+    // The fee is deducted from the amount Bob receives
+    client
+        .transfer(&alice, &alice_account, &bob_account, 10 * TOKEN)
+        .await
+        .unwrap();
+    assert_balances(&client, &alice, &alice_account, 0, 0, 90 * TOKEN).await;
+    assert_balances(&client, &bob, &bob_account, 0, 10 * TOKEN - TOKEN / 10, 0).await;
+
+    // The withheld fee moves from Bob's account to the mint, then to the fee account
+    client.harvest_fees(&[bob_account]).await.unwrap();
+    let withdrawn = client
+        .withdraw_fees(client.payer(), &fee_account)
+        .await
+        .unwrap();
+    assert_eq!(withdrawn, TOKEN / 10);
+    assert_balances(&client, client.payer(), &fee_account, 0, 0, TOKEN / 10).await;
+}
+
+#[tokio::test]
+async fn audit_transfer_amount() {
+    let auditor = ElGamalKeypair::new_rand();
+    let (context, client, transactions) = setup_audited(&auditor, None).await;
+    let alice = funded_keypair(&context).await;
+    let bob = funded_keypair(&context).await;
+
+    let alice_account = client
+        .create_account(
+            &alice,
+            DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER,
+            false,
+        )
+        .await
+        .unwrap()
+        .token_account;
+    let bob_account = client
+        .create_account(&bob, DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER, false)
+        .await
+        .unwrap()
+        .token_account;
+    client
+        .mint_to(client.payer(), &alice_account, 100 * TOKEN)
+        .await
+        .unwrap();
+    client
+        .deposit(&alice, &alice_account, 100 * TOKEN)
+        .await
+        .unwrap();
+
+    transactions.lock().unwrap().clear();
+    client
+        .transfer(&alice, &alice_account, &bob_account, 42 * TOKEN)
+        .await
+        .unwrap();
+
+    // Only the transfer instruction carries the amount encrypted for the auditor
+    let transfers: Vec<_> = transactions
+        .lock()
+        .unwrap()
+        .iter()
+        .flat_map(|transaction| {
+            find_confidential_transfers(&VersionedTransaction::from(transaction.clone())).unwrap()
+        })
+        .collect();
+    assert_eq!(transfers.len(), 1);
+    let transfer = &transfers[0];
+    assert_eq!(transfer.source, alice_account);
+    assert_eq!(&transfer.mint, client.mint());
+    assert_eq!(transfer.destination, bob_account);
+    assert!(!transfer.with_fee);
+    assert_eq!(transfer.decrypt_amount(&auditor).unwrap(), 42 * TOKEN);
+}
+
+#[tokio::test]
+async fn audit_transfer_with_fee_amount() {
+    // 1% transfer fee, capped at 1 token
+    let auditor = ElGamalKeypair::new_rand();
+    let (context, client, transactions) = setup_audited(&auditor, Some((100, TOKEN))).await;
+    let alice = funded_keypair(&context).await;
+    let bob = funded_keypair(&context).await;
+
+    let alice_account = client
+        .create_account(
+            &alice,
+            DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER,
+            false,
+        )
+        .await
+        .unwrap()
+        .token_account;
+    let bob_account = client
+        .create_account(&bob, DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER, false)
+        .await
+        .unwrap()
+        .token_account;
+    client
+        .mint_to(client.payer(), &alice_account, 100 * TOKEN)
+        .await
+        .unwrap();
+    client
+        .deposit(&alice, &alice_account, 100 * TOKEN)
+        .await
+        .unwrap();
+
+    transactions.lock().unwrap().clear();
+    client
+        .transfer(&alice, &alice_account, &bob_account, 10 * TOKEN)
+        .await
+        .unwrap();
+
+    // The auditor sees the full amount, including the fee withheld in Bob's account
+    let transfers: Vec<_> = transactions
+        .lock()
+        .unwrap()
+        .iter()
+        .flat_map(|transaction| {
+            find_confidential_transfers(&VersionedTransaction::from(transaction.clone())).unwrap()
+        })
+        .collect();
+    assert_eq!(transfers.len(), 1);
+    let transfer = &transfers[0];
+    assert_eq!(transfer.source, alice_account);
+    assert_eq!(transfer.destination, bob_account);
+    assert!(transfer.with_fee);
+    assert_eq!(transfer.decrypt_amount(&auditor).unwrap(), 10 * TOKEN);
+}
+
+#[tokio::test]
+async fn dry_run_changes_nothing() {
+    let (context, client) = setup().await;
+    let alice = funded_keypair(&context).await;
+    let bob = funded_keypair(&context).await;
+
+    let alice_account = client
+        .create_account(
+            &alice,
+            DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER,
+            false,
+        )
+        .await
+        .unwrap()
+        .token_account;
+    let bob_account = client
+        .create_account(&bob, DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER, false)
+        .await
+        .unwrap()
+        .token_account;
+    client
+        .mint_to(client.payer(), &alice_account, 10 * TOKEN)
+        .await
+        .unwrap();
+    client
+        .deposit(&alice, &alice_account, 4 * TOKEN)
+        .await
+        .unwrap();
+
+    // A simulated deposit is only reported
+    let dry_run_client = ConfidentialClient::with_program_client(
+        Arc::new(ProgramBanksClient::new_from_context(
+            context.clone(),
+            ProgramBanksClientSimulate,
+        )),
+        *client.mint(),
+        client.payer().insecure_clone(),
+        DECIMALS,
+    );
+    dry_run_client
+        .deposit(&alice, &alice_account, 6 * TOKEN)
+        .await
+        .unwrap();
+    assert_balances(&client, &alice, &alice_account, 6 * TOKEN, 0, 4 * TOKEN).await;
+
+    // A simulated proof context state account does not exist for the next transaction, so a
+    // transfer stops with an error instead of going on past the failed step
+    assert!(dry_run_client
+        .transfer(&alice, &alice_account, &bob_account, 3 * TOKEN)
+        .await
+        .is_err());
+
+    // A failed simulation maps to the same typed error as a failed transaction
+    client
+        .set_confidential_credits(&alice, &alice_account, false)
+        .await
+        .unwrap();
+    assert!(matches!(
+        dry_run_client
+            .deposit(&alice, &alice_account, 6 * TOKEN)
+            .await,
+        Err(ConfidentialError::ConfidentialCreditsDisabled)
+    ));
+
+    // The bank itself is untouched
+    assert_balances(&client, &alice, &alice_account, 6 * TOKEN, 0, 4 * TOKEN).await;
+    assert_balances(&client, &bob, &bob_account, 0, 0, 0).await;
+}
+
+#[tokio::test]
+async fn empty_and_close_account() {
+    let (context, client) = setup().await;
+    let alice = funded_keypair(&context).await;
+    let bob = funded_keypair(&context).await;
+
+    let alice_account = client
+        .create_account(
+            &alice,
+            DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER,
+            false,
+        )
+        .await
+        .unwrap()
+        .token_account;
+    let bob_account = client
+        .create_account(&bob, DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER, false)
+        .await
+        .unwrap()
+        .token_account;
+
+    client
+        .mint_to(client.payer(), &alice_account, 10 * TOKEN)
+        .await
+        .unwrap();
+    client
+        .deposit(&alice, &alice_account, 10 * TOKEN)
+        .await
+        .unwrap();
+    client
+        .transfer(&alice, &alice_account, &bob_account, 10 * TOKEN)
+        .await
+        .unwrap();
+
+    // Bob has not applied the transfer yet
+    let result = client.close_account(&bob, &bob_account).await;
+    assert!(matches!(
+        result,
+        Err(ConfidentialError::PendingBalanceNotApplied)
+    ));
+
+    // Alice's account is empty, closing it returns the rent to her
+    let program_client = client.program_client();
+    let rent = program_client
+        .get_account(alice_account)
+        .await
+        .unwrap()
+        .unwrap()
+        .lamports;
+    let lamports_before = program_client
+        .get_account(alice.pubkey())
+        .await
+        .unwrap()
+        .unwrap()
+        .lamports;
+
+    client.close_account(&alice, &alice_account).await.unwrap();
+    assert!(program_client
+        .get_account(alice_account)
+        .await
+        .unwrap()
+        .is_none());
+    let lamports_after = program_client
+        .get_account(alice.pubkey())
+        .await
+        .unwrap()
+        .unwrap()
+        .lamports;
+    assert_eq!(lamports_after, lamports_before + rent);
+}
+
+#[tokio::test]
+async fn toggle_credits() {
+    let (context, client) = setup().await;
+    let alice = funded_keypair(&context).await;
+    let bob = funded_keypair(&context).await;
+
+    let alice_account = client
+        .create_account(
+            &alice,
+            DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER,
+            false,
+        )
+        .await
+        .unwrap()
+        .token_account;
+    let bob_account = client
+        .create_account(&bob, DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER, false)
+        .await
+        .unwrap()
+        .token_account;
+
+    // Bob refuses confidential transfers, the transfer is rejected before generating proofs
+    client
+        .set_confidential_credits(&bob, &bob_account, false)
+        .await
+        .unwrap();
+    let result = client
+        .transfer(&alice, &alice_account, &bob_account, TOKEN)
+        .await;
+    assert!(matches!(
+        result,
+        Err(ConfidentialError::ConfidentialCreditsDisabled)
+    ));
+
+    // Bob refuses public credits, public transfers to his account fail until he accepts them
+    client
+        .mint_to(client.payer(), &alice_account, TOKEN)
+        .await
+        .unwrap();
+
+    client
+        .set_non_confidential_credits(&bob, &bob_account, false)
+        .await
+        .unwrap();
+    let result = client
+        .token()
+        .transfer(
+            &alice_account,
+            &bob_account,
+            &alice.pubkey(),
+            TOKEN,
+            &[&alice],
+        )
+        .await;
+    assert!(result.is_err());
+
+    client
+        .set_non_confidential_credits(&bob, &bob_account, true)
+        .await
+        .unwrap();
+    client
+        .token()
+        .transfer(
+            &alice_account,
+            &bob_account,
+            &alice.pubkey(),
+            TOKEN,
+            &[&alice],
+        )
+        .await
+        .unwrap();
+    assert_eq!(client.public_balance(&bob_account).await.unwrap(), TOKEN);
+}
+
+/// Deposits into the pending balance without applying it. Returns whether the deposit succeeded.
+async fn deposit_without_apply(
+    client: &BanksConfidentialClient,
+    owner: &Keypair,
+    account: &Pubkey,
+    amount: u64,
+) -> bool {
+    client
+        .token()
+        .confidential_transfer_deposit(account, &owner.pubkey(), amount, DECIMALS, &[owner])
+        .await
+        .is_ok()
+}
+
+#[tokio::test]
+async fn apply_pending_before_credit_limit() {
+    let (context, client) = setup().await;
+    let alice = funded_keypair(&context).await;
+
+    // Alice's account accepts two credits before its pending balance must be applied
+    let alice_account = client
+        .create_account(&alice, 2, false)
+        .await
+        .unwrap()
+        .token_account;
+    client
+        .mint_to(client.payer(), &alice_account, 10 * TOKEN)
+        .await
+        .unwrap();
+
+    // Deposits without applying, each one is a pending balance credit
+    assert!(deposit_without_apply(&client, &alice, &alice_account, TOKEN).await);
+
+    // Nothing to do while more than one credit is left
+    assert!(!client
+        .apply_pending_if_near_limit(&alice, &alice_account, 0)
+        .await
+        .unwrap());
+
+    assert!(deposit_without_apply(&client, &alice, &alice_account, TOKEN).await);
+
+    // A third credit is rejected (MaximumPendingBalanceCreditCounterExceeded)
+    assert!(!deposit_without_apply(&client, &alice, &alice_account, TOKEN).await);
+
+    // At the limit the pending balance is applied and credits are accepted again
+    assert!(client
+        .apply_pending_if_near_limit(&alice, &alice_account, 0)
+        .await
+        .unwrap());
+    assert_balances(&client, &alice, &alice_account, 8 * TOKEN, 0, 2 * TOKEN).await;
+    assert!(deposit_without_apply(&client, &alice, &alice_account, TOKEN).await);
+
+    // With one credit of headroom the watcher applies before the limit is hit
+    assert!(client
+        .apply_pending_if_near_limit(&alice, &alice_account, 1)
+        .await
+        .unwrap());
+    assert_balances(&client, &alice, &alice_account, 7 * TOKEN, 0, 3 * TOKEN).await;
+}
+
+#[tokio::test]
+async fn associated_token_account() {
+    // The associated token account program sizes the account from the return data of
+    // Token-2022, which the native processor cannot set, so this runs the bundled programs
+    let (context, client) = setup_on(
+        ProgramTest::default(),
+        ProgramBanksClientProcessWithPreflight,
+        None,
+        false,
+        None,
+    )
+    .await;
+    let alice = funded_keypair(&context).await;
+
+    let res = client
+        .create_account(&alice, DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER, true)
+        .await
+        .unwrap();
+
+    // The account is found from the owner and mint alone, there is no keypair to keep
+    let alice_account = associated_token_address(&alice.pubkey(), client.mint());
+    assert_eq!(res.token_account, alice_account);
+    assert!(res.token_account_kp.is_none());
+
+    client
+        .mint_to(client.payer(), &alice_account, 10 * TOKEN)
+        .await
+        .unwrap();
+    client
+        .deposit(&alice, &alice_account, 4 * TOKEN)
+        .await
+        .unwrap();
+    assert_balances(&client, &alice, &alice_account, 6 * TOKEN, 0, 4 * TOKEN).await;
+
+    // Rotating would leave the associated token address closed, so it is refused untouched
+    assert!(matches!(
+        client.rotate_account(&alice, &alice_account, false).await,
+        Err(ConfidentialError::Invalid(_))
+    ));
+    assert_balances(&client, &alice, &alice_account, 6 * TOKEN, 0, 4 * TOKEN).await;
+}
+
+#[tokio::test]
+async fn recover_keys_from_owner() {
+    let (context, client) = setup().await;
+    let alice = funded_keypair(&context).await;
+    let bob = funded_keypair(&context).await;
+
+    let res = client
+        .create_account(
+            &alice,
+            DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER,
+            false,
+        )
+        .await
+        .unwrap();
+    let alice_account = res.token_account;
+    client
+        .mint_to(client.payer(), &alice_account, 10 * TOKEN)
+        .await
+        .unwrap();
+    client
+        .deposit(&alice, &alice_account, 4 * TOKEN)
+        .await
+        .unwrap();
+
+    // The owner keypair and the account address are enough to rebuild both keys
+    let recovered = client.recover_keys(&alice, &alice_account).await.unwrap();
+    assert_eq!(
+        recovered.user_elgamal_kp.pubkey(),
+        res.user_elgamal_kp.pubkey()
+    );
+    assert_eq!(
+        recovered.user_aes_kp.encrypt(0).decrypt(&res.user_aes_kp),
+        Some(0)
+    );
+
+    // Another keypair is rejected before any key is derived
+    assert!(matches!(
+        client.recover_keys(&bob, &alice_account).await,
+        Err(ConfidentialError::Invalid(_))
+    ));
+
+    // After an owner change the keys derived from the new owner do not match the account
+    client
+        .token()
+        .set_authority(
+            &alice_account,
+            &alice.pubkey(),
+            Some(&bob.pubkey()),
+            AuthorityType::AccountOwner,
+            &[&alice],
+        )
+        .await
+        .unwrap();
+    assert!(matches!(
+        client.recover_keys(&bob, &alice_account).await,
+        Err(ConfidentialError::ElGamalPubkeyMismatch)
+    ));
+}
+
+#[tokio::test]
+async fn rotate_elgamal_key() {
+    let (context, client) = setup().await;
+    let alice = funded_keypair(&context).await;
+    let bob = funded_keypair(&context).await;
+
+    let old = client
+        .create_account(
+            &alice,
+            DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER,
+            false,
+        )
+        .await
+        .unwrap();
+    let old_account = old.token_account;
+    let bob_account = client
+        .create_account(&bob, DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER, false)
+        .await
+        .unwrap()
+        .token_account;
+
+    client
+        .mint_to(client.payer(), &old_account, 10 * TOKEN)
+        .await
+        .unwrap();
+    client
+        .deposit(&alice, &old_account, 4 * TOKEN)
+        .await
+        .unwrap();
+    client
+        .mint_to(client.payer(), &bob_account, 2 * TOKEN)
+        .await
+        .unwrap();
+    client.deposit(&bob, &bob_account, 2 * TOKEN).await.unwrap();
+    client
+        .transfer(&bob, &bob_account, &old_account, 2 * TOKEN)
+        .await
+        .unwrap();
+
+    // Public, available and the unapplied pending balance all move to the new account
+    let new = client
+        .rotate_account(&alice, &old_account, false)
+        .await
+        .unwrap();
+    assert_ne!(new.token_account, old_account);
+    assert_ne!(new.user_elgamal_kp.pubkey(), old.user_elgamal_kp.pubkey());
+    assert_balances(&client, &alice, &new.token_account, 6 * TOKEN, 0, 6 * TOKEN).await;
+    assert!(client
+        .program_client()
+        .get_account(old_account)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn rotate_on_fee_mint_requires_allowing_the_fee() {
+    // 1% transfer fee, capped at 1 token
+    let (context, client) = setup_with(false, Some((100, TOKEN))).await;
+    let alice = funded_keypair(&context).await;
+    let bob = funded_keypair(&context).await;
+
+    let old_account = client
+        .create_account(
+            &alice,
+            DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER,
+            false,
+        )
+        .await
+        .unwrap()
+        .token_account;
+    let bob_account = client
+        .create_account(&bob, DEFAULT_MAXIMUM_PENDING_BALANCE_CREDIT_COUNTER, false)
+        .await
+        .unwrap()
+        .token_account;
+    client
+        .mint_to(client.payer(), &old_account, 10 * TOKEN)
+        .await
+        .unwrap();
+    client
+        .deposit(&alice, &old_account, 10 * TOKEN)
+        .await
+        .unwrap();
+
+    // A public transfer into the old account withholds its fee there
+    client
+        .mint_to(client.payer(), &bob_account, 5 * TOKEN)
+        .await
+        .unwrap();
+    client
+        .token()
+        .transfer(
+            &bob_account,
+            &old_account,
+            &bob.pubkey(),
+            5 * TOKEN,
+            &[&bob],
+        )
+        .await
+        .unwrap();
+    let public = 5 * TOKEN - 5 * TOKEN / 100;
+
+    // Refused before anything is created, the old account is untouched
+    assert!(matches!(
+        client.rotate_account(&alice, &old_account, false).await,
+        Err(ConfidentialError::Invalid(_))
+    ));
+    assert_balances(&client, &alice, &old_account, public, 0, 10 * TOKEN).await;
+
+    // Once allowed, the fee is withheld from the moved balances and the fees withheld in the
+    // old account are harvested, so it can be closed
+    let new = client
+        .rotate_account(&alice, &old_account, true)
+        .await
+        .unwrap();
+    assert_balances(
+        &client,
+        &alice,
+        &new.token_account,
+        public - public / 100,
+        0,
+        10 * TOKEN - TOKEN / 10,
+    )
+    .await;
+    assert!(client
+        .program_client()
+        .get_account(old_account)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn parse_token_amounts() {
+    assert_eq!(parse_token_amount("12.345", 6).unwrap(), 12_345_000);
+    assert_eq!(parse_token_amount(".5", 6).unwrap(), 500_000);
+    assert_eq!(parse_token_amount("12.", 6).unwrap(), 12_000_000);
+
+    // Mints without decimals only take whole amounts
+    assert_eq!(parse_token_amount("5", 0).unwrap(), 5);
+    assert!(parse_token_amount("5.1", 0).is_err());
+
+    // More decimal places than the mint has
+    assert!(parse_token_amount("1.1234567", 6).is_err());
+
+    // Amounts beyond u64::MAX base units fail instead of wrapping
+    assert!(parse_token_amount("18446744073709551616", 0).is_err());
+    assert!(parse_token_amount("18446744073709.551616", 6).is_err());
+    assert_eq!(
+        parse_token_amount("18446744073709.551615", 6).unwrap(),
+        u64::MAX
+    );
+
+    for invalid in ["", ".", "-1", "1e3", "1.2.3"] {
+        assert!(
+            parse_token_amount(invalid, 6).is_err(),
+            "{invalid:?} parsed"
+        );
+    }
+}
+
+#[test]
+fn keystore_round_trip() {
+    let owner = Keypair::new();
+    let account = Pubkey::new_unique();
+    let (elgamal_kp, aes_kp) = derive_confidential_keys(&owner, &account).unwrap();
+    let secrets = KeystoreSecrets {
+        owner: owner.insecure_clone(),
+        elgamal_kp,
+        aes_kp,
+    };
+
+    let mut keystore = Keystore::encrypt(&secrets, "correct horse battery staple").unwrap();
+    assert!(keystore.decrypt("wrong passphrase").is_err());
+
+    // Iterations outside the accepted range are rejected before deriving the key
+    for iterations in [1, 599_999, 10_000_001, u32::MAX] {
+        keystore.iterations = iterations;
+        let error = keystore
+            .decrypt("correct horse battery staple")
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("KDF iterations"), "{error}");
+    }
+    keystore.iterations = 600_000;
+
+    // The passphrase restores the owner keypair and both confidential keys
+    let decrypted = keystore.decrypt("correct horse battery staple").unwrap();
+    assert_eq!(decrypted.owner.pubkey(), owner.pubkey());
+    assert_eq!(decrypted.elgamal_kp.secret(), secrets.elgamal_kp.secret());
+    assert_eq!(decrypted.elgamal_kp.pubkey(), secrets.elgamal_kp.pubkey());
+    assert_eq!(decrypted.aes_kp, secrets.aes_kp);
+}
+
+#[test]
+fn rpc_config_precedence() {
+    let config_file = env::temp_dir().join(format!("config-{}.yml", Pubkey::new_unique()));
+    fs::write(
+        &config_file,
+        "json_rpc_url: http://10.0.0.2:8899\n\
+         websocket_url: ws://10.0.0.2:8900/\n\
+         keypair_path: id.json\n\
+         address_labels: {}\n\
+         commitment: finalized\n",
+    )
+    .unwrap();
+
+    // The config file is used when no flag is given
+    let rpc_config = RpcConfig::resolve(Some(&config_file), None, None, None).unwrap();
+    assert_eq!(rpc_config.url, "http://10.0.0.2:8899");
+    assert_eq!(rpc_config.ws_url, "ws://10.0.0.2:8900/");
+    assert_eq!(rpc_config.commitment, CommitmentConfig::finalized());
+
+    // Flags win, and the WebSocket URL follows --url instead of the config file
+    let rpc_config = RpcConfig::resolve(
+        Some(&config_file),
+        Some("localhost"),
+        None,
+        Some("processed"),
+    )
+    .unwrap();
+    assert_eq!(rpc_config.url, "http://localhost:8899");
+    assert_eq!(rpc_config.ws_url, "ws://localhost:8900/");
+    assert_eq!(rpc_config.commitment, CommitmentConfig::processed());
+
+    let rpc_config =
+        RpcConfig::resolve(Some(&config_file), None, Some("ws://10.0.0.3:8900"), None).unwrap();
+    assert_eq!(rpc_config.url, "http://10.0.0.2:8899");
+    assert_eq!(rpc_config.ws_url, "ws://10.0.0.3:8900");
+
+    fs::remove_file(&config_file).unwrap();
+    assert!(RpcConfig::resolve(Some(&config_file), None, None, None).is_err());
+}
+
+#[tokio::test]
+async fn funding_skips_funded_accounts() {
+    // The mock RPC client reports a balance of 50 lamports for every address
+    let recipient = Pubkey::new_unique();
+    let airdrop = FundingSource::Airdrop {
+        deadline: Duration::from_secs(2),
+    };
+
+    let rpc_client = RpcClient::new_mock(String::from("succeeds"));
+    let sig = fund_account(&rpc_client, &recipient, 50, &airdrop)
+        .await
+        .unwrap();
+    assert!(sig.is_none());
+    let sig = fund_account(&rpc_client, &recipient, LAMPORTS_PER_SOL, &airdrop)
+        .await
+        .unwrap();
+    assert!(sig.is_some());
+
+    // A failed airdrop is reported, and an unconfirmed one gives up at the deadline
+    let rpc_client = RpcClient::new_mock(String::from("instruction_error"));
+    assert!(
+        fund_account(&rpc_client, &recipient, LAMPORTS_PER_SOL, &airdrop)
+            .await
+            .is_err()
+    );
+    let rpc_client = RpcClient::new_mock(String::from("sig_not_found"));
+    assert!(
+        fund_account(&rpc_client, &recipient, LAMPORTS_PER_SOL, &airdrop)
+            .await
+            .is_err()
+    );
+
+    // A funder without enough lamports is rejected before sending anything
+    let rpc_client = RpcClient::new_mock(String::from("succeeds"));
+    let funder = FundingSource::Funder(Box::new(Keypair::new()));
+    assert!(
+        fund_account(&rpc_client, &recipient, LAMPORTS_PER_SOL, &funder)
+            .await
+            .is_err()
+    );
+    let sig = fund_account(&rpc_client, &recipient, 50, &funder)
+        .await
+        .unwrap();
+    assert!(sig.is_none());
+}